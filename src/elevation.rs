@@ -0,0 +1,210 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use geo::Point;
+
+use crate::graph::{EdgeId, Graph};
+
+/// SRTM tiles mark missing measurements with this value
+const VOID: i16 = i16::MIN;
+
+/// Digital elevation model assembled from local SRTM tiles (.hgt)
+/// note: GeoTIFF tiles are not supported yet, convert them with gdal_translate
+pub struct Dem {
+    /// key is the south west corner of a tile as (lat, lon)
+    tiles: HashMap<(i16, i16), Tile>,
+}
+
+/// One degree by one degree square of height samples
+struct Tile {
+    /// samples per row and per column, 1201 for SRTM3 and 3601 for SRTM1
+    size: usize,
+    /// rows are sorted from north to south, columns from west to east
+    samples: Vec<i16>,
+}
+
+impl Dem {
+    /// Load every .hgt file inside the directory at [path]
+    pub fn from_dir(path: &str) -> Self {
+        let entries = fs::read_dir(path)
+            .expect("Could not open elevation directory");
+
+        let mut tiles: HashMap<(i16, i16), Tile> = HashMap::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let corner = match corner_from(&path) {
+                Some(c) => c,
+                None => continue, // not a tile
+            };
+
+            match fs::read(&path) {
+                Ok(bytes) => match Tile::from_bytes(&bytes) {
+                    Some(tile) => { tiles.insert(corner, tile); },
+                    None => eprintln!("{:?} is not a valid SRTM tile", path),
+                },
+                Err(e) => eprintln!("{:?}", e),
+            }
+        }
+
+        Self { tiles }
+    }
+
+    /// Height above sea level in meters, interpolated between the four
+    /// surrounding samples
+    pub fn elevation(&self, p: &Point) -> Option<f64> {
+        let corner = (p.y().floor() as i16, p.x().floor() as i16);
+        let tile = self.tiles.get(&corner)?;
+
+        // position inside the tile, measured in samples from the north west
+        let steps = (tile.size - 1) as f64;
+        let row = (corner.0 as f64 + 1.0 - p.y()) * steps;
+        let col = (p.x() - corner.1 as f64) * steps;
+
+        let r0 = (row.floor() as usize).min(tile.size - 2);
+        let c0 = (col.floor() as usize).min(tile.size - 2);
+        let dr = row - r0 as f64;
+        let dc = col - c0 as f64;
+
+        let nw = tile.sample(r0, c0)?;
+        let ne = tile.sample(r0, c0 + 1)?;
+        let sw = tile.sample(r0 + 1, c0)?;
+        let se = tile.sample(r0 + 1, c0 + 1)?;
+
+        let north = nw * (1.0 - dc) + ne * dc;
+        let south = sw * (1.0 - dc) + se * dc;
+        Some(north * (1.0 - dr) + south * dr)
+    }
+}
+
+impl Tile {
+    /// Tiles are square grids of big endian 16 bit integers
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let count = bytes.len() / 2;
+        let size = (count as f64).sqrt() as usize;
+        if size < 2 || size * size != count { return None; }
+
+        let samples = bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_be_bytes([b[0], b[1]]))
+            .collect();
+
+        Some(Self { size, samples })
+    }
+
+    fn sample(&self, row: usize, col: usize) -> Option<f64> {
+        match self.samples[row * self.size + col] {
+            VOID => None,
+            h => Some(h as f64),
+        }
+    }
+}
+
+/// south west corner (lat, lon) of a tile, encoded in its file name
+/// like N51E007.hgt
+fn corner_from(path: &Path) -> Option<(i16, i16)> {
+    if path.extension()?.to_str()?.to_lowercase() != "hgt" { return None; }
+    let name = path.file_stem()?.to_str()?.to_uppercase();
+    if name.len() != 7 { return None; }
+
+    let lat: i16 = name.get(1..3)?.parse().ok()?;
+    let lon: i16 = name.get(4..7)?.parse().ok()?;
+
+    let lat = match name.get(0..1)? {
+        "N" => lat,
+        "S" => -lat,
+        _ => return None,
+    };
+    let lon = match name.get(3..4)? {
+        "E" => lon,
+        "W" => -lon,
+        _ => return None,
+    };
+
+    Some((lat, lon))
+}
+
+/// Store the height of every graph node, nodes outside of the loaded tiles
+/// stay without elevation. Afterwards every edge knows its height gain and
+/// loss
+pub fn annotate(graph: &mut Graph, dem: &Dem) {
+    for node in graph.nodes_mut().values_mut() {
+        if let Some(h) = dem.elevation(node.point()) {
+            node.set_elevation(h);
+        }
+    }
+
+    let climbs: Vec<(EdgeId, f64, f64)> = graph.edges()
+        .iter()
+        .map(|(edge_id, edge)| {
+            let heights: Vec<Option<f64>> = edge.nodes()
+                .iter()
+                .map(|node_id| *graph.nodes().get(node_id).unwrap().elevation())
+                .collect();
+            let (ascent, descent) = climb(&heights);
+            (*edge_id, ascent, descent)
+        })
+        .collect();
+
+    for (edge_id, ascent, descent) in climbs {
+        graph.edges_mut().get_mut(&edge_id).unwrap().set_climb(ascent, descent);
+    }
+}
+
+/// Summed up height gain and loss along consecutive heights, unknown heights
+/// are skipped
+pub fn climb(heights: &[Option<f64>]) -> (f64, f64) {
+    let mut ascent = 0.0;
+    let mut descent = 0.0;
+
+    let known: Vec<f64> = heights.iter().flatten().copied().collect();
+    for pair in known.windows(2) {
+        let diff = pair[1] - pair[0];
+        if diff > 0.0 { ascent += diff; } else { descent -= diff; }
+    }
+
+    (ascent, descent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 3x3 tile with heights rising from west to east
+    fn tile() -> Tile {
+        let heights: [i16; 9] = [
+            0, 100, 200,
+            0, 100, 200,
+            0, 100, VOID,
+        ];
+        let bytes: Vec<u8> = heights.iter().flat_map(|h| h.to_be_bytes()).collect();
+        Tile::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn corner_from_file_name() {
+        assert_eq!(Some((51, 7)), corner_from(Path::new("dem/N51E007.hgt")));
+        assert_eq!(Some((-12, -77)), corner_from(Path::new("S12W077.HGT")));
+        assert_eq!(None, corner_from(Path::new("N51E007.tif")));
+        assert_eq!(None, corner_from(Path::new("readme.hgt")));
+    }
+
+    #[test]
+    fn elevation_interpolated() {
+        let dem = Dem { tiles: HashMap::from([((51, 7), tile())]) };
+
+        // south west corner of the tile
+        assert_eq!(Some(0.0), dem.elevation(&Point::new(7.0, 51.0)));
+        // a quarter between the first two columns
+        assert_eq!(Some(25.0), dem.elevation(&Point::new(7.125, 51.75)));
+        // next to a void sample there is no height
+        assert_eq!(None, dem.elevation(&Point::new(7.9, 51.1)));
+        // no tile loaded for this area
+        assert_eq!(None, dem.elevation(&Point::new(8.5, 51.5)));
+    }
+
+    #[test]
+    fn climb_skips_unknown_heights() {
+        let heights = vec![Some(10.0), Some(15.0), None, Some(12.0), Some(20.0)];
+        assert_eq!((13.0, 3.0), climb(&heights));
+        assert_eq!((0.0, 0.0), climb(&[None, Some(3.0)]));
+    }
+}
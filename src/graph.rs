@@ -1,8 +1,25 @@
 use std::{u8, collections::HashMap};
 
+// spatial lookup of nodes and edges
+mod index;
+// which parts of the network are reachable from each other
+mod components;
+// roads through areas, a route must not enter
+mod areas;
+
+pub use index::{EdgeMatch, SpatialIndex};
+
+use geo::HaversineDistance;
+
 pub type NodeId = u64;
 pub type EdgeId = u64;
 
+/// Node inserted by splitting an edge, it is not part of OpenStreetMap
+pub const VIRTUAL_NODE: NodeId = u64::MAX;
+/// Way chunk indices of the two halves of a split edge, chunks of real ways
+/// never come close to these (ways have at most 2000 nodes)
+const VIRTUAL_CHUNKS: (u64, u64) = (2046, 2047);
+
 /// A graphs node
 pub struct Node {
     /// Identifies exactly one node, identical to OSM NodeId
@@ -18,6 +35,8 @@ pub struct Node {
     /// till 255 (best surroundings imaginable)
     /// note: May be outsourced to hexagonal grid in future
     greatness: u8,
+    /// Height above sea level in meters, if an elevation model covers the node
+    elevation: Option<f64>,
 }
 
 impl Node {
@@ -31,7 +50,8 @@ impl Node {
             id,
             point,
             edges: Vec::new(),
-            greatness: 0
+            greatness: 0,
+            elevation: None
         }
     }
     /// Coordinate data storage and for calculation of geometric properties
@@ -57,6 +77,13 @@ impl Node {
     pub fn greatness(&self) -> &u8 {
         &self.greatness
     }
+    /// Height above sea level in meters, if an elevation model covers the node
+    pub fn elevation(&self) -> &Option<f64> {
+        &self.elevation
+    }
+    pub fn set_elevation(&mut self, elevation: f64) {
+        self.elevation = Some(elevation);
+    }
 }
 
 /// An edge consists of multiple nodes: Two intersection nodes (s, t) and
@@ -72,7 +99,13 @@ pub struct Edge {
     /// If true, the edge goes from s-->t and not reachable from t-//->s 
     directed: bool,
     /// sorted listing of nodes n.first = s --> ... --> n.last = t
-    nodes: Vec<NodeId>
+    nodes: Vec<NodeId>,
+    /// Summed up height gain in meters when going s-->t
+    ascent: f64,
+    /// Summed up height loss in meters when going s-->t
+    descent: f64,
+    /// OpenStreetMap tags of the way, the edge is part of
+    tags: osmpbfreader::Tags
 }
 
 impl Edge {
@@ -88,13 +121,19 @@ impl Edge {
             id,
             distance,
             directed,
-            nodes
+            nodes,
+            ascent: 0.0,
+            descent: 0.0,
+            tags: osmpbfreader::Tags::new()
         }    
     }
     
     /// OpenStreetMap tags
     pub fn tags(&self) -> &osmpbfreader::Tags {
-        todo!();
+        &self.tags
+    }
+    pub fn set_tags(&mut self, tags: osmpbfreader::Tags) {
+        self.tags = tags;
     }
     /// Section length between the Edge's two nodes
     pub fn distance(&self) -> &f64 {
@@ -120,30 +159,139 @@ impl Edge {
     pub fn nodes(&self) -> &Vec<NodeId> {
         &self.nodes
     }
+    /// Height gain in meters, going s-->t if [forward] and t-->s otherwise
+    pub fn ascent(&self, forward: bool) -> f64 {
+        if forward { self.ascent } else { self.descent }
+    }
+    /// Set height gain and loss when going s-->t
+    pub fn set_climb(&mut self, ascent: f64, descent: f64) {
+        self.ascent = ascent;
+        self.descent = descent;
+    }
 }
 
 pub struct Graph {
     nodes: HashMap<NodeId, Node>,
-    edges: HashMap<EdgeId, Edge>
+    edges: HashMap<EdgeId, Edge>,
+    /// Proximity lookup, built once from the geometry of nodes and edges
+    index: SpatialIndex,
 }
 
 impl Graph {
-    /// Create empty graph
+    /// Create graph and index its geometry
     pub fn new(
         nodes: HashMap<NodeId, Node>,
         edges: HashMap<EdgeId, Edge>
     ) -> Self {
+        let index = SpatialIndex::new(&nodes, &edges);
         Self {
             nodes,
-            edges
+            edges,
+            index,
+        }
+    }
+
+    pub fn index(&self) -> &SpatialIndex {
+        &self.index
+    }
+
+    /// Keep only the edges, [keep] is true for. Nodes forget removed edges
+    /// and vanish, if none is left, the index is built again
+    fn retain_edges(&mut self, keep: impl Fn(&EdgeId, &Edge) -> bool) {
+        self.edges.retain(|edge_id, edge| keep(edge_id, edge));
+        let edges = &self.edges;
+        for node in self.nodes.values_mut() {
+            node.edges.retain(|edge_id| edges.contains_key(edge_id));
         }
+        self.nodes.retain(|_, node| ! node.edges.is_empty());
+        self.index = SpatialIndex::new(&self.nodes, &self.edges);
+    }
+
+    /// Insert the [VIRTUAL_NODE] at the position of [at] and replace the
+    /// split edge by two new edges, each covering one part of it. The index
+    /// is built again, so it knows the new parts instead of the old edge.
+    /// There is only one virtual node, so a graph can be split once.
+    /// Returns the end of the edge instead, if [at] is right on it
+    pub fn split_edge(&mut self, at: &EdgeMatch) -> NodeId {
+        let edge = self.edges.get(&at.edge_id).unwrap();
+        if at.offset < 0.5 { return *edge.s(); }
+        if *edge.distance() - at.offset < 0.5 { return *edge.t(); }
+
+        // find the piece of the edge, that contains the split position
+        let point = |node_id: &NodeId| *self.nodes.get(node_id).unwrap().point();
+        let mut i = 0;
+        let mut walked = 0.0;
+        while i < edge.nodes().len() - 2 {
+            let piece = point(&edge.nodes()[i]).haversine_distance(&point(&edge.nodes()[i + 1]));
+            if walked + piece > at.offset { break; }
+            walked += piece;
+            i += 1;
+        }
+
+        // the height is interpolated along the piece
+        let before = self.nodes.get(&edge.nodes()[i]).unwrap();
+        let after = self.nodes.get(&edge.nodes()[i + 1]).unwrap();
+        let piece = before.point().haversine_distance(after.point());
+        let elevation = match (before.elevation(), after.elevation()) {
+            (Some(b), Some(a)) if piece > 0.0 => Some(b + (a - b) * (at.offset - walked) / piece),
+            (b, a) => (*b).or(*a),
+        };
+
+        let mut first: Vec<NodeId> = edge.nodes()[..=i].to_vec();
+        first.push(VIRTUAL_NODE);
+        let mut second: Vec<NodeId> = vec![VIRTUAL_NODE];
+        second.extend_from_slice(&edge.nodes()[i + 1..]);
+
+        // keep the way id in the lower bits for tag lookups
+        let way_id = at.edge_id & ((1u64 << 53) - 1);
+        let halves = [
+            ((VIRTUAL_CHUNKS.0 << 53) | way_id, at.offset, first),
+            ((VIRTUAL_CHUNKS.1 << 53) | way_id, *edge.distance() - at.offset, second),
+        ];
+        let directed = *edge.directed();
+        let tags = edge.tags().clone();
+
+        let mut node = Node::new(VIRTUAL_NODE, at.projection);
+        if let Some(h) = elevation { node.set_elevation(h); }
+        self.nodes.insert(VIRTUAL_NODE, node);
+
+        for (edge_id, distance, nodes) in halves {
+            let mut half = Edge::new(edge_id, distance, directed, nodes);
+            half.set_tags(tags.clone());
+            let heights: Vec<Option<f64>> = half.nodes()
+                .iter()
+                .map(|node_id| *self.nodes.get(node_id).unwrap().elevation())
+                .collect();
+            let (ascent, descent) = crate::elevation::climb(&heights);
+            half.set_climb(ascent, descent);
+
+            // every node of a half belongs to it instead of the split edge
+            for node_id in half.nodes() {
+                let node = self.nodes.get_mut(node_id).unwrap();
+                node.edges.retain(|e| *e != at.edge_id);
+                node.insert_edge(edge_id);
+            }
+            self.edges.insert(edge_id, half);
+        }
+        self.edges.remove(&at.edge_id);
+        self.index = SpatialIndex::new(&self.nodes, &self.edges);
+
+        VIRTUAL_NODE
     }
     
     pub fn nodes(&self) -> &HashMap<NodeId, Node> {
         &self.nodes
     }
 
+    pub fn nodes_mut(&mut self) -> &mut HashMap<NodeId, Node> {
+        &mut self.nodes
+    }
+
     pub fn edges(&self) -> &HashMap<NodeId, Edge> {
         &self.edges
     }
+
+    pub fn edges_mut(&mut self) -> &mut HashMap<EdgeId, Edge> {
+        &mut self.edges
+    }
 }
\ No newline at end of file
@@ -0,0 +1,58 @@
+use std::collections::HashSet;
+
+use geo::{Intersects, LineString, Polygon};
+
+use crate::graph::{EdgeId, Graph};
+
+impl Graph {
+    /// Remove every edge, that enters one of the [areas], e.g. a busy square
+    /// or a private estate. Returns the number of removed edges
+    pub fn avoid(&mut self, areas: &[Polygon]) -> usize {
+        let before = self.edges.len();
+        let nodes = &self.nodes;
+        let crossing: HashSet<EdgeId> = self.edges
+            .iter()
+            .filter(|(_, edge)| {
+                let line: LineString = edge.nodes()
+                    .iter()
+                    .map(|node_id| *nodes.get(node_id).unwrap().point())
+                    .collect();
+                areas.iter().any(|area| area.intersects(&line))
+            })
+            .map(|(edge_id, _)| *edge_id)
+            .collect();
+        self.retain_edges(|edge_id, _| ! crossing.contains(edge_id));
+
+        before - self.edges.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use geo::Point;
+    use crate::graph::{Node, Edge, NodeId};
+
+    #[test]
+    fn avoid_removes_crossing_edges() {
+        // 0<-->1<-->2, the area covers the middle of the second edge
+        let mut nodes: HashMap<NodeId, Node> = HashMap::new();
+        for node_id in 0..3 {
+            nodes.insert(node_id, Node::new(node_id, Point::new(7.0 + node_id as f64 * 0.01, 51.0)));
+        }
+        let mut edges: HashMap<EdgeId, Edge> = HashMap::new();
+        for (edge_id, s, t) in [(0, 0, 1), (1, 1, 2)] {
+            edges.insert(edge_id, Edge::new(edge_id, 700.0, false, vec![s, t]));
+            nodes.get_mut(&s).unwrap().insert_edge(edge_id);
+            nodes.get_mut(&t).unwrap().insert_edge(edge_id);
+        }
+        let mut graph = Graph::new(nodes, edges);
+
+        let area = Polygon::new(LineString::from(vec![(7.014, 50.99), (7.016, 50.99), (7.016, 51.01), (7.014, 51.01)]), vec![]);
+        assert_eq!(1, graph.avoid(&[area]));
+        assert!(graph.edges().contains_key(&0));
+        assert!(! graph.nodes().contains_key(&2));
+        assert_eq!(&vec![0], graph.nodes().get(&1).unwrap().edges());
+    }
+}
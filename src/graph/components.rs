@@ -0,0 +1,197 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::graph::{Graph, NodeId};
+
+impl Graph {
+    /// Groups of intersections, where every member can be reached from every
+    /// other member of the same group, respecting one-way streets
+    pub fn strongly_connected_components(&self) -> Vec<Vec<NodeId>> {
+        let mut roots: Vec<NodeId> = self.edges
+            .values()
+            .flat_map(|edge| [*edge.s(), *edge.t()])
+            .collect();
+        roots.sort();
+        roots.dedup();
+
+        let mut tarjan = Tarjan::new(self);
+        for root in roots {
+            if ! tarjan.order.contains_key(&root) { tarjan.run(root); }
+        }
+        tarjan.components
+    }
+
+    /// Every intersection, that can be reached from [node_id] and leads back
+    /// to it, including [node_id] itself. Only searches around [node_id]
+    /// instead of computing every component of the network
+    pub fn component_of(&self, node_id: &NodeId) -> HashSet<NodeId> {
+        if ! self.nodes.contains_key(node_id) { return HashSet::new(); }
+
+        let forward = self.search(node_id, |n| self.successors(n));
+        let backward = self.search(node_id, |n| self.predecessors(n));
+        forward.intersection(&backward).copied().collect()
+    }
+
+    /// Remove components with less than [min_size] intersections, e.g. the
+    /// paths of a park with gated entrances, together with all of their edges.
+    /// Returns the number of removed intersections
+    pub fn drop_islands(&mut self, min_size: usize) -> usize {
+        let dropped: HashSet<NodeId> = self.strongly_connected_components()
+            .into_iter()
+            .filter(|component| component.len() < min_size)
+            .flatten()
+            .collect();
+        if dropped.is_empty() { return 0; }
+
+        self.retain_edges(|_, edge| ! dropped.contains(edge.s()) && ! dropped.contains(edge.t()));
+
+        dropped.len()
+    }
+
+    /// Every intersection found from [start] by following [next]
+    fn search(&self, start: &NodeId, next: impl Fn(&NodeId) -> Vec<NodeId>) -> HashSet<NodeId> {
+        let mut found = HashSet::from([*start]);
+        let mut stack = vec![*start];
+        while let Some(node_id) = stack.pop() {
+            for neighbour in next(&node_id) {
+                if found.insert(neighbour) { stack.push(neighbour); }
+            }
+        }
+        found
+    }
+
+    /// Intersections, from which [node_id] can be entered directly
+    fn predecessors(&self, node_id: &NodeId) -> Vec<NodeId> {
+        self.nodes.get(node_id).unwrap().edges
+            .iter()
+            .filter_map(|edge_id| self.edges.get(edge_id))
+            .filter_map(|edge| {
+                if edge.t() == node_id { return Some(*edge.s()); }
+                if edge.s() == node_id && ! edge.directed() { return Some(*edge.t()); }
+                None
+            })
+            .collect()
+    }
+
+    /// Intersections, that can be entered directly from [node_id]
+    fn successors(&self, node_id: &NodeId) -> Vec<NodeId> {
+        self.nodes.get(node_id).unwrap().edges
+            .iter()
+            .filter_map(|edge_id| self.edges.get(edge_id))
+            .filter_map(|edge| {
+                if edge.s() == node_id { return Some(*edge.t()); }
+                if edge.t() == node_id && ! edge.directed() { return Some(*edge.s()); }
+                // node in the middle of the edge or wrong way of a one-way street
+                None
+            })
+            .collect()
+    }
+}
+
+/// Tarjan's algorithm without recursion, large networks would overflow the stack
+struct Tarjan<'a> {
+    graph: &'a Graph,
+    /// position of every visited node in the depth first search
+    order: HashMap<NodeId, usize>,
+    /// smallest position reachable from the subtree of the node
+    low: HashMap<NodeId, usize>,
+    stack: Vec<NodeId>,
+    on_stack: HashSet<NodeId>,
+    /// nodes of the search path together with successors not yet looked at
+    frames: Vec<(NodeId, std::vec::IntoIter<NodeId>)>,
+    components: Vec<Vec<NodeId>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn new(graph: &'a Graph) -> Self {
+        Self {
+            graph,
+            order: HashMap::new(),
+            low: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            frames: Vec::new(),
+            components: Vec::new(),
+        }
+    }
+
+    fn discover(&mut self, node_id: NodeId) {
+        let position = self.order.len();
+        self.order.insert(node_id, position);
+        self.low.insert(node_id, position);
+        self.stack.push(node_id);
+        self.on_stack.insert(node_id);
+        self.frames.push((node_id, self.graph.successors(&node_id).into_iter()));
+    }
+
+    fn run(&mut self, root: NodeId) {
+        self.discover(root);
+
+        while let Some((node_id, successors)) = self.frames.last_mut() {
+            let node_id = *node_id;
+            match successors.next() {
+                Some(next) if ! self.order.contains_key(&next) => self.discover(next),
+                Some(next) => if self.on_stack.contains(&next) {
+                    let low = self.low[&node_id].min(self.order[&next]);
+                    self.low.insert(node_id, low);
+                },
+                None => {
+                    self.frames.pop();
+                    if let Some((parent, _)) = self.frames.last() {
+                        let low = self.low[parent].min(self.low[&node_id]);
+                        self.low.insert(*parent, low);
+                    }
+                    // node is the root of a component
+                    if self.low[&node_id] == self.order[&node_id] {
+                        let mut component = Vec::new();
+                        loop {
+                            let member = self.stack.pop().unwrap();
+                            self.on_stack.remove(&member);
+                            component.push(member);
+                            if member == node_id { break; }
+                        }
+                        self.components.push(component);
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::Point;
+    use crate::graph::{Node, Edge, EdgeId};
+
+    #[test]
+    fn components_respect_one_ways() {
+        // 0<-->1<-->2--->3<-->4    5<-->6
+        let mut nodes: HashMap<NodeId, Node> = HashMap::new();
+        for node_id in 0..7 {
+            nodes.insert(node_id, Node::new(node_id, Point::new(7.0 + node_id as f64 * 0.001, 51.0)));
+        }
+        let mut edges: HashMap<EdgeId, Edge> = HashMap::new();
+        for (edge_id, s, t, directed) in [(0, 0, 1, false), (1, 1, 2, false), (2, 2, 3, true), (3, 3, 4, false), (4, 5, 6, false)] {
+            edges.insert(edge_id, Edge::new(edge_id, 70.0, directed, vec![s, t]));
+            nodes.get_mut(&s).unwrap().insert_edge(edge_id);
+            nodes.get_mut(&t).unwrap().insert_edge(edge_id);
+        }
+        let mut graph = Graph::new(nodes, edges);
+
+        let mut components: Vec<Vec<NodeId>> = graph.strongly_connected_components()
+            .into_iter()
+            .map(|mut component| { component.sort(); component })
+            .collect();
+        components.sort();
+        assert_eq!(vec![vec![0, 1, 2], vec![3, 4], vec![5, 6]], components);
+        assert_eq!(HashSet::from([3, 4]), graph.component_of(&4));
+        assert_eq!(HashSet::from([0, 1, 2]), graph.component_of(&0));
+
+        assert_eq!(4, graph.drop_islands(3));
+        let mut left: Vec<NodeId> = graph.nodes().keys().copied().collect();
+        left.sort();
+        assert_eq!(vec![0, 1, 2], left);
+        assert_eq!(&vec![1], graph.nodes().get(&2).unwrap().edges());
+        assert_eq!(2, graph.index().nearest_nodes(&Point::new(7.006, 51.0)).next().unwrap());
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use geo::{Closest, ClosestPoint, HaversineDistance, Line, Point};
+use rstar::primitives::GeomWithData;
+use rstar::RTree;
+
+use crate::graph::{Edge, EdgeId, Node, NodeId};
+
+/// Meters per degree of latitude, used to turn radii into degrees
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// One straight piece of an edge and the meters along the edge to its start
+type Segment = GeomWithData<Line, (EdgeId, f64)>;
+
+/// R-trees over the geometry of the graph for fast proximity queries.
+/// Coordinates are stored in a plane, where longitudes are shrunk to the
+/// length of latitudes at the center of the map, so that distances inside
+/// of the trees are proportional to meters
+pub struct SpatialIndex {
+    nodes: RTree<GeomWithData<Point, NodeId>>,
+    segments: RTree<Segment>,
+    /// factor from longitudes to the plane
+    scale: f64,
+}
+
+/// Closest position on an edge to some query point
+#[derive(Clone, Copy, Debug)]
+pub struct EdgeMatch {
+    pub edge_id: EdgeId,
+    /// Point on the edge closest to the query point
+    pub projection: Point,
+    /// Meters between the query point and the projection
+    pub distance: f64,
+    /// Meters from the first node of the edge along its geometry
+    pub offset: f64,
+}
+
+impl SpatialIndex {
+    pub fn new(nodes: &HashMap<NodeId, Node>, edges: &HashMap<EdgeId, Edge>) -> Self {
+        let latitude = match nodes.is_empty() {
+            true => 0.0,
+            false => nodes.values().map(|node| node.point().y()).sum::<f64>() / nodes.len() as f64,
+        };
+        let scale = latitude.to_radians().cos();
+        let to_plane = |p: &Point| Point::new(p.x() * scale, p.y());
+
+        let node_points = nodes
+            .iter()
+            .map(|(node_id, node)| GeomWithData::new(to_plane(node.point()), *node_id))
+            .collect();
+
+        let mut segments: Vec<Segment> = Vec::new();
+        for (edge_id, edge) in edges {
+            let mut offset = 0.0;
+            for pair in edge.nodes().windows(2) {
+                let start = nodes.get(&pair[0]).unwrap().point();
+                let end = nodes.get(&pair[1]).unwrap().point();
+                segments.push(GeomWithData::new(
+                    Line::new(to_plane(start), to_plane(end)),
+                    (*edge_id, offset)
+                ));
+                offset += start.haversine_distance(end);
+            }
+        }
+
+        Self {
+            nodes: RTree::bulk_load(node_points),
+            segments: RTree::bulk_load(segments),
+            scale,
+        }
+    }
+
+    /// Nodes ordered by their distance to [p], nearest first. Take the first
+    /// k for a k-nearest query
+    pub fn nearest_nodes(&self, p: &Point) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes
+            .nearest_neighbor_iter(&self.to_plane(p))
+            .map(|node| node.data)
+    }
+
+    /// The edge closest to [p] with the position on it
+    pub fn nearest_edge(&self, p: &Point) -> Option<EdgeMatch> {
+        let segment = self.segments.nearest_neighbor(&self.to_plane(p))?;
+        self.edge_match(segment, p)
+    }
+
+    /// Every edge passing within [radius] meters of [p], with its closest
+    /// position. Nearest edges come first
+    pub fn edges_within(&self, p: &Point, radius: f64) -> Vec<EdgeMatch> {
+        let degrees = radius / METERS_PER_DEGREE;
+
+        let mut matches: Vec<EdgeMatch> = Vec::new();
+        for segment in self.segments.locate_within_distance(self.to_plane(p), degrees * degrees) {
+            let candidate = match self.edge_match(segment, p) {
+                Some(c) if c.distance <= radius => c,
+                _ => continue,
+            };
+
+            // only the closest segment of every edge is of interest
+            match matches.iter_mut().find(|m| m.edge_id == candidate.edge_id) {
+                Some(known) => if candidate.distance < known.distance { *known = candidate; },
+                None => matches.push(candidate),
+            }
+        }
+
+        matches.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        matches
+    }
+
+    /// Locate the projection of [p] on the [segment]
+    fn edge_match(&self, segment: &Segment, p: &Point) -> Option<EdgeMatch> {
+        let (edge_id, offset) = segment.data;
+        let projection = match segment.geom().closest_point(&self.to_plane(p)) {
+            Closest::Intersection(c) | Closest::SinglePoint(c) => self.to_geographic(&c),
+            Closest::Indeterminate => return None,
+        };
+        let start = self.to_geographic(&segment.geom().start_point());
+
+        Some(EdgeMatch {
+            edge_id,
+            projection,
+            distance: p.haversine_distance(&projection),
+            offset: offset + start.haversine_distance(&projection),
+        })
+    }
+
+    fn to_plane(&self, p: &Point) -> Point {
+        Point::new(p.x() * self.scale, p.y())
+    }
+
+    fn to_geographic(&self, p: &Point) -> Point {
+        Point::new(p.x() / self.scale, p.y())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{Node as GraphNode, Edge as GraphEdge};
+
+    #[test]
+    fn edges_within_radius() {
+        // 0---1---2  and the separate edge 3---4 about 110 m further north
+        let mut graph_nodes: HashMap<NodeId, GraphNode> = HashMap::new();
+        graph_nodes.insert(0, GraphNode::new(0, Point::new(7.000, 51.000)));
+        graph_nodes.insert(1, GraphNode::new(1, Point::new(7.001, 51.000)));
+        graph_nodes.insert(2, GraphNode::new(2, Point::new(7.002, 51.000)));
+        graph_nodes.insert(3, GraphNode::new(3, Point::new(7.000, 51.001)));
+        graph_nodes.insert(4, GraphNode::new(4, Point::new(7.002, 51.001)));
+
+        let mut graph_edges: HashMap<EdgeId, GraphEdge> = HashMap::new();
+        graph_edges.insert(0, GraphEdge::new(0, 140.0, false, vec![0, 1, 2]));
+        graph_edges.insert(1, GraphEdge::new(1, 140.0, false, vec![3, 4]));
+        let index = SpatialIndex::new(&graph_nodes, &graph_edges);
+
+        let p = Point::new(7.0014, 51.0001);
+        let matches = index.edges_within(&p, 50.0);
+        assert_eq!(1, matches.len());
+        assert_eq!(0, matches[0].edge_id);
+        assert!((matches[0].distance - 11.1).abs() < 0.5);
+        // 1.4 of 2 pieces with about 70 m each
+        assert!((matches[0].offset - 98.0).abs() < 1.0);
+
+        let matches = index.edges_within(&p, 150.0);
+        assert_eq!(vec![0, 1], matches.iter().map(|m| m.edge_id).collect::<Vec<EdgeId>>());
+
+        assert_eq!(0, index.nearest_edge(&p).unwrap().edge_id);
+        assert_eq!(vec![1, 2, 0], index.nearest_nodes(&p).take(3).collect::<Vec<NodeId>>());
+        assert_eq!(Some(4), index.nearest_nodes(&p).find(|node_id| *node_id > 2));
+    }
+}
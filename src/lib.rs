@@ -1,14 +1,23 @@
 use core::panic;
-use std::{error::Error, fs::File, io::{BufReader, Read}, println, path::Path};
+use std::{collections::HashSet, error::Error, fs::File, io::{BufReader, Read}, println, path::Path};
 
-use geo::Point;
-use graph::{Graph, NodeId};
-use parser::OsmData;
+use geo::{HaversineDistance, Point, Polygon};
+use graph::{EdgeId, Graph, NodeId};
+use parser::{OsmData, Via};
+use profile::Profile;
+use router::{Direction, Shape, Stops, Strategy};
+use router::postprocessor::{CueFormat, Format, GpxContent, Simplification, Trip};
 
+// height data for the map
+mod elevation;
 // map data structure
 mod graph;
 // make osm.pbf files useable
 mod parser;
+// bike specific riding properties
+mod profile;
+// statistics of a finished route
+mod report;
 // all routing algorithms are implemented here
 mod router;
 
@@ -18,54 +27,243 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
         &config.pbf
     );
     
-    let graph: Graph = parser::weave(&mut data);
-    let start_point = Point::new(        
-        config.start_lon.clone(),
-        config.start_lat.clone()
-    );
-
+    let mut graph: Graph = parser::weave(&mut data);
+    if let Some(dem) = &config.dem {
+        elevation::annotate(&mut graph, &elevation::Dem::from_dir(dem));
+    }
+    // roads through areas to avoid are not ridden at all
+    let mut areas: Vec<Polygon> = config.avoid
+        .iter()
+        .map(|ring| Polygon::new(ring.iter().map(|[lon, lat]| (*lon, *lat)).collect(), vec![]))
+        .collect();
+    if let Some(path) = &config.avoid_file {
+        areas.extend(parser::areas_from_geojson(path));
+    }
+    let avoided = match areas.is_empty() {
+        true => 0,
+        false => graph.avoid(&areas),
+    };
+    let islands = graph.drop_islands(MIN_COMPONENT_SIZE);
+    // an imported route starts where its track begins
+    let track: Option<Vec<Point>> = config.import.as_ref().map(|path| parser::track_from_gpx(path));
+    let start_point = match track.as_ref().and_then(|t| t.first()) {
+        Some(p) => *p,
+        None => Point::new(
+            config.start_lon.clone(),
+            config.start_lat.clone()
+        ),
+    };
+    // the loop begins and ends exactly at the closest position on a way
+    let (start, snapping) = router::snap(&mut graph, &start_point);
+    // pois are only visited, if the route can get there and back again
+    let reachable: HashSet<NodeId> = graph.component_of(&start);
+    // a trip to a destination ends at the intersection closest to it
+    let destination: Option<NodeId> = match (config.end_lat, config.end_lon) {
+        (Some(lat), Some(lon)) => Some(router::closest_intersection(&graph, &Point::new(lon, lat), &reachable)?),
+        _ => None,
+    };
+    if destination.is_some() && config.shape.is_some() {
+        return Err("a shape can only be ridden as a loop".into());
+    }
+    let end = destination.unwrap_or(start);
+    // via points are passed at the intersection closest to them
+    let via: Vec<NodeId> = config.via
+        .iter()
+        .map(|via| router::closest_intersection(&graph, &via.point(&data), &reachable))
+        .collect::<Result<_, _>>()?;
+    if ! via.is_empty() && config.shape.is_some() {
+        return Err("a shape passes no via points".into());
+    }
+    let stops = Stops { start, via, end };
+    let end_point = *graph.nodes().get(&end).unwrap().point();
 
+    // kilometers, a trip to a destination is a bit longer than the beeline,
+    // if no distance is wished
+    let distance: f64 = match (config.distance, destination) {
+        (Some(d), _) => d as f64,
+        (None, Some(_)) => start_point.haversine_distance(&end_point) * SCENIC_FACTOR / 1000.0,
+        // an imported track is analyzed as it is, nothing is planned
+        (None, None) if track.is_some() => 0.0,
+        (None, None) => return Err("a generated roundtrip needs a distance".into()),
+    };
     // real_travel_distance is in meters, so convert the expected distance to meters
     // and add 10% to it
-    let expected_travel_distance = (config.distance as f64 * 0_900.0, config.distance as f64 * 1_100.0);
-    let mut real_travel_distance = f64::MAX;
-    let mut gpx: Option<gpx::Gpx> = None;
+    let expected_travel_distance = (distance * 0_900.0, distance * 1_100.0);
+    // the climbing may differ by 25% from the wished ascent
+    let expected_ascent = config.ascent.map(|a| (a as f64 * 0.75, a as f64 * 1.25));
+    let mut interesting_points: Vec<parser::Poi> = Vec::new();
+    let mut visit: Vec<NodeId> = Vec::new();
+    let mut route: router::Path;
+
+    let gradient_penalty = match (config.gradient_penalty, config.ascent) {
+        (Some(p), _) => p,
+        (None, Some(0)) => FLAT_GRADIENT_PENALTY,
+        (None, _) => GRADIENT_PENALTY,
+    };
+    // without elevation data every route is flat
+    if config.ascent.is_some() && config.dem.is_none() {
+        return Err("a wished ascent needs elevation data (dem)".into());
+    }
+    let mut weighting = match config.dem {
+        Some(_) => router::Weighting::new(&config.profile, gradient_penalty),
+        None => router::Weighting::flat(),
+    };
 
     println!("exp {:?}", expected_travel_distance);
-    
-    while real_travel_distance < expected_travel_distance.0 ||
-          real_travel_distance > expected_travel_distance.1 {
-        let interesting_points = parser::interesting_surrounding(&data, &start_point, &config.distance);
-        let mut visit = router::nearest_graph_nodes(&graph, &interesting_points);
+    // without a wished heading every shaped loop goes somewhere else
+    let heading = config.heading.unwrap_or_else(|| rand::random::<f64>() * 360.0);
+    // routes planned so far, the weighting is adjusted for the wished ascent
+    let mut attempts = 0;
+    // whether the last planned trip misses the wished distance
+    let mut outside_window = false;
+    // waypoints of the last planned trip, that can not be reached
+    let mut skipped: Vec<NodeId> = Vec::new();
+    // ascent of the last planned trip, if it misses the wished one
+    let mut missed_ascent: Option<f64> = None;
+
+    loop {
+        // imported routes are analyzed and exported as they are
+        if let Some(track) = &track {
+            route = router::match_track(&graph, track)?;
+            (interesting_points, visit) = pois_along(&data, &graph, &route);
+            break;
+        }
 
-        let start = router::closest_point(&graph, &start_point);
-        let route: Vec<NodeId> = router::unoptimized(&graph, &mut visit, &start);
+        let plan = match &config.shape {
+            Some(shape) => router::shaped(
+                &graph, &weighting, &start, &reachable, &router::Outline { shape: *shape, heading }, expected_travel_distance, &config.direction
+            )?,
+            None => {
+                interesting_points = match destination {
+                    Some(_) => parser::interesting_between(&data, &start_point, &end_point, expected_travel_distance.1),
+                    None => parser::interesting_surrounding(&data, &start_point, distance),
+                };
+                let points: Vec<Point> = interesting_points.iter().map(|poi| poi.point).collect();
+                visit = router::nearest_graph_nodes(&graph, &points, &reachable)?;
 
-        gpx = Some(router::postprocessor::intersections_to_gpx(&graph, &route));
-        let ls = gpx.clone().unwrap().routes.first().unwrap().linestring();
+                match config.strategy {
+                    Strategy::Unoptimized => {
+                        // the more kilometers, the more pois
+                        let mut few: Vec<NodeId> = visit.iter().take(4 + distance as usize / 12).copied().collect();
+                        router::unoptimized(&graph, &weighting, &mut few, &stops, expected_travel_distance, &config.direction)
+                    },
+                    Strategy::Insertion => router::roundtrip(&graph, &weighting, &stops, &visit, expected_travel_distance, &config.direction),
+                    Strategy::Orienteering => router::orienteering(&graph, &weighting, &stops, &visit, expected_travel_distance, &config.direction)?,
+                }
+            },
+        };
+        outside_window = ! plan.fits;
+        skipped = plan.skipped;
+        // spurs, repetitions and figure eights are ridden differently, if possible
+        route = router::repair(&graph, &weighting, &plan.route, expected_travel_distance.1);
 
-        // check if start and end is equal
-        if ls.0.first() != ls.0.last() { continue; }
+        // "as flat as possible" (ascent = 0) accepts every route
+        attempts += 1;
+        if let Some((min, max)) = expected_ascent.filter(|(_, max)| *max > 0.0) {
+            let (real_ascent, _) = Trip::new(&graph, &route, Vec::new()).elevation_gain();
+            if attempts < CLIMB_ATTEMPTS {
+                if real_ascent < min { weighting.prefer_climbs(); continue; }
+                if real_ascent > max { weighting.avoid_climbs(); continue; }
+            }
+            missed_ascent = Some(real_ascent).filter(|ascent| *ascent < min || *ascent > max);
+        }
 
-        // if more than 25% of points are visited twice, it is not a nice route
-        let mut ls2 = ls.0.clone();
-        ls2.dedup();
-        if ls2.len() < ls.0.len() * 0.75 as usize { continue; }
-        
-        real_travel_distance = geo::algorithm::HaversineLength::haversine_length(
-            &mut gpx.clone().unwrap().routes.first().unwrap().linestring()
-        );
-        println!("dist is {}", real_travel_distance);
+        break;
+    }
+    // a shaped loop visits the pois it passes by
+    if config.shape.is_some() {
+        (interesting_points, visit) = pois_along(&data, &graph, &route);
     }
 
-    
-    let gpx_path = Path::new(&config.result);
-    let gpx_file = match File::create(&gpx_path) {
-        Err(why) => panic!("couldn't create path: {}", why),
-        Ok(file) => file,
-    };
+    let mut report = report::Report::new(&graph, &route, &visit);
+    report.set_snapping(snapping);
+    report.set_islands(islands);
+    report.set_avoided(avoided);
+    if outside_window {
+        let (min, max) = expected_travel_distance;
+        report.add_warning(format!("no trip between {:.1} and {:.1} km was found", min / 1000.0, max / 1000.0));
+    }
+    for node_id in skipped {
+        let p = graph.nodes().get(&node_id).unwrap().point();
+        report.add_warning(format!("the intersection at ({:.5}, {:.5}) can not be reached and is left out", p.y(), p.x()));
+    }
+    if let (Some(ascent), Some((min, max))) = (missed_ascent, expected_ascent) {
+        report.add_warning(format!("the route climbs {:.0} m instead of {:.0} to {:.0} m", ascent, min, max));
+    }
+    if track.is_none() && report.repeated() > MAX_REPEATED {
+        report.add_warning(format!("{:.0} % of the route are ridden more than once", report.repeated() * 100.0));
+    }
 
-    gpx::write(&gpx.unwrap(), gpx_file).ok();
+    // only the interesting points, the route really passes by
+    let passed: HashSet<NodeId> = route.nodes().iter().copied().collect();
+    let visited: Vec<parser::Poi> = interesting_points
+        .iter()
+        .zip(visit.iter())
+        .filter(|(_, node_id)| passed.contains(node_id))
+        .map(|(poi, _)| poi.clone())
+        .collect();
+    let mut trip = Trip::new(&graph, &route, visited);
+
+    // the cue sheet needs every intersection, so it is created before simplifying
+    let cues = router::postprocessor::trip_to_cues(&graph, &trip);
+    if let Some(path) = &config.cues {
+        std::fs::write(path, router::postprocessor::cues_to_string(&cues, &config.cue_format))?;
+    }
+
+    if config.simplify.is_some() || config.max_points.is_some() {
+        let kept = trip.simplify(&config.simplify_method, config.simplify.unwrap_or(0.0), config.max_points);
+        if let Some(max_points) = config.max_points.filter(|max_points| kept > *max_points) {
+            report.add_warning(format!("the route keeps {} points, more than the maximum of {}", kept, max_points));
+        }
+    }
+
+    print!("{}", report);
+    if let Some(path) = &config.report {
+        std::fs::write(path, report.to_json())?;
+    }
+
+    match config.format {
+        Format::Gpx => {
+            let mut gpx = router::postprocessor::trip_to_gpx(&trip, &config.gpx_content);
+
+            // key values are shown by gpx viewers
+            let summary = report.summary(&config.profile);
+            for route in gpx.routes.iter_mut() { route.description = Some(summary.clone()); }
+            for track in gpx.tracks.iter_mut() { track.description = Some(summary.clone()); }
+            gpx.metadata.as_mut().unwrap().description = Some(summary);
+
+            if config.cues.is_some() {
+                gpx.routes.push(router::postprocessor::cues_to_route(&cues));
+            }
+
+            let gpx_path = Path::new(&config.result);
+            let gpx_file = match File::create(&gpx_path) {
+                Err(why) => panic!("couldn't create path: {}", why),
+                Ok(file) => file,
+            };
+
+            gpx::write(&gpx, gpx_file).ok();
+        },
+        Format::GeoJson => {
+            let hull = match config.debug_hull {
+                true => Some(router::concave_hull_of(&graph, &visit)),
+                false => None,
+            };
+            let geojson = router::postprocessor::trip_to_geojson(&graph, &trip, hull.as_ref());
+            std::fs::write(&config.result, geojson)?;
+        },
+        Format::Kml => {
+            std::fs::write(&config.result, router::postprocessor::trip_to_kml(&trip))?;
+        },
+        Format::Tcx => {
+            let tcx = router::postprocessor::trip_to_tcx(&trip, &config.profile);
+            std::fs::write(&config.result, tcx)?;
+        },
+        Format::Fit => {
+            let fit = router::postprocessor::trip_to_fit(&trip, &config.profile);
+            std::fs::write(&config.result, fit)?;
+        },
+    }
 
     Ok(())
 }
@@ -73,11 +271,103 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
 /// Runtime configuration
 #[derive(serde::Deserialize)]
 pub struct Config {
-    distance: u8,
+    /// wished length in kilometers, only optional for a trip to a destination
+    distance: Option<u8>,
     start_lat: f64,
     start_lon: f64,
+    /// destination of a one-way trip, the route is a loop without it
+    end_lat: Option<f64>,
+    end_lon: Option<f64>,
+    /// places the route passes in any case, positions or OpenStreetMap nodes
+    /// and ways, e.g. { lat = 51.48, lon = 7.41 } or { node = 283102981 }
+    #[serde(default)]
+    via: Vec<Via>,
+    /// areas the route never enters, rings of [lon, lat] positions
+    #[serde(default)]
+    avoid: Vec<Vec<[f64; 2]>>,
+    /// GeoJSON file with further areas to avoid
+    avoid_file: Option<String>,
     pbf: String,
-    result: String
+    result: String,
+    /// directory containing SRTM tiles (.hgt), routes stay flat without it
+    dem: Option<String>,
+    /// kind of bike, limits how steep climbs may be
+    #[serde(default)]
+    profile: Profile,
+    /// wished total ascent in meters, 0 for a route as flat as possible.
+    /// Needs elevation data from [dem]
+    ascent: Option<u16>,
+    /// additional cost in meters for each meter of ascent
+    gradient_penalty: Option<f64>,
+    /// path of the route statistics as json file
+    report: Option<String>,
+    /// write the route as gpx route, track, route of decision points or both
+    #[serde(default)]
+    gpx_content: GpxContent,
+    /// file format of the result
+    #[serde(default)]
+    format: Format,
+    /// add the concave hull, the route is planned with, to the geojson output
+    #[serde(default)]
+    debug_hull: bool,
+    /// path of the turn by turn cue sheet, also added as gpx route
+    cues: Option<String>,
+    /// file format of the cue sheet
+    #[serde(default)]
+    cue_format: CueFormat,
+    /// tolerance in meters, points closer to the simplified line are dropped
+    simplify: Option<f64>,
+    /// algorithm used for the simplification
+    #[serde(default)]
+    simplify_method: Simplification,
+    /// upper limit of points in the result, e.g. for bike computers
+    max_points: Option<usize>,
+    /// how the pois to visit are chosen and ordered
+    #[serde(default)]
+    strategy: Strategy,
+    /// way round the loop is ridden
+    #[serde(default)]
+    direction: Direction,
+    /// plan a loop along this outline instead of visiting pois
+    shape: Option<Shape>,
+    /// bearing in degrees from the start to the far end of the shape, 0 is
+    /// north and 90 is east, random if not set
+    heading: Option<f64>,
+    /// gpx file of an existing route, that is matched onto the map instead of
+    /// planning a new one
+    import: Option<String>,
+}
+
+/// One meter uphill costs as much as riding this many meters on the flat
+const GRADIENT_PENALTY: f64 = 8.0;
+/// Penalty used for routes, that shall be as flat as possible
+const FLAT_GRADIENT_PENALTY: f64 = 40.0;
+/// Routes planned with adjusted weighting, before the climbing is accepted
+/// as it is
+const CLIMB_ATTEMPTS: usize = 10;
+/// Pois closer than this many meters to an imported or shaped route are
+/// passed by
+const POI_CORRIDOR: f64 = 50.0;
+/// A trip to a destination without wished distance is this many times as
+/// long as the beeline, so there is room for a scenic detour
+const SCENIC_FACTOR: f64 = 1.5;
+/// Share of a planned route, that may be ridden more than once without a
+/// warning
+const MAX_REPEATED: f64 = 0.25;
+/// Parts of the network with fewer intersections are islands, that can not
+/// be reached from the rest, e.g. the paths of a gated park
+const MIN_COMPONENT_SIZE: usize = 50;
+
+/// Every poi close to the [route] with the node it is passed at
+fn pois_along(data: &OsmData, graph: &Graph, route: &router::Path) -> (Vec<parser::Poi>, Vec<NodeId>) {
+    let ridden: HashSet<EdgeId> = route.edges().iter().copied().collect();
+    parser::interesting_points(data)
+        .into_iter()
+        .filter_map(|poi| {
+            let node_id = router::passed_by(graph, &ridden, &poi.point, POI_CORRIDOR)?;
+            Some((poi, node_id))
+        })
+        .unzip()
 }
 
 impl Config {
@@ -3,5 +3,8 @@ use std::fs::File;
 fn main() {
     let f = File::open("resources/config.toml").unwrap();
     let config = nice_bike_roundtrips::Config::from(f);
-    nice_bike_roundtrips::run(config);
-}
\ No newline at end of file
+    if let Err(e) = nice_bike_roundtrips::run(config) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
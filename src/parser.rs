@@ -2,13 +2,19 @@ use std::{fs::File, io::BufReader, process::exit};
 use core::panic;
 use osmpbfreader::{OsmPbfReader, OsmObj};
 
+mod areas;
 mod data;
 mod network;
 mod surrounding;
+mod track;
+mod via;
 
+pub use crate::parser::areas::*;
 pub use crate::parser::data::*;
 pub use crate::parser::network::*;
 pub use crate::parser::surrounding::*;
+pub use crate::parser::track::*;
+pub use crate::parser::via::*;
 
 /// Returns a container of every Node, Way and Realation in an pbf file.
 /// note: could be optimized to return just a somewhat useful subset to reduce
@@ -0,0 +1,82 @@
+use geo::{Coord, LineString, Polygon};
+use serde_json::Value;
+
+/// Every polygon of a GeoJSON file, e.g. areas a route must not enter
+pub fn areas_from_geojson(path: &str) -> Vec<Polygon> {
+    let text = std::fs::read_to_string(path)
+            .expect("Could not find .geojson file");
+    let value: Value = serde_json::from_str(&text)
+            .expect("Could not read .geojson file");
+
+    polygons_of(&value)
+}
+
+/// Polygons and multipolygons of features, collections and geometries,
+/// other geometries are skipped
+fn polygons_of(value: &Value) -> Vec<Polygon> {
+    match value["type"].as_str() {
+        Some("FeatureCollection") => value["features"]
+            .as_array()
+            .map(|features| features.iter().flat_map(polygons_of).collect())
+            .unwrap_or_default(),
+        Some("Feature") => polygons_of(&value["geometry"]),
+        Some("GeometryCollection") => value["geometries"]
+            .as_array()
+            .map(|geometries| geometries.iter().flat_map(polygons_of).collect())
+            .unwrap_or_default(),
+        Some("Polygon") => polygon(&value["coordinates"]).into_iter().collect(),
+        Some("MultiPolygon") => value["coordinates"]
+            .as_array()
+            .map(|polygons| polygons.iter().filter_map(polygon).collect())
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Polygon of GeoJSON coordinates, an outer ring followed by its holes
+fn polygon(coordinates: &Value) -> Option<Polygon> {
+    let mut rings = coordinates.as_array()?.iter().map(ring);
+    let exterior = rings.next()??;
+    let interiors: Option<Vec<LineString>> = rings.collect();
+    Some(Polygon::new(exterior, interiors?))
+}
+
+/// Ring of [lon, lat] positions
+fn ring(positions: &Value) -> Option<LineString> {
+    positions
+        .as_array()?
+        .iter()
+        .map(|position| Some(Coord { x: position[0].as_f64()?, y: position[1].as_f64()? }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::Contains;
+    use geo::Point;
+
+    #[test]
+    fn polygons_of_features() {
+        let value: Value = serde_json::from_str(r#"{
+            "type": "FeatureCollection",
+            "features": [
+                { "type": "Feature", "properties": {}, "geometry": { "type": "Point", "coordinates": [7.0, 51.0] } },
+                { "type": "Feature", "properties": {}, "geometry": { "type": "Polygon", "coordinates": [
+                    [[7.0, 51.0], [7.1, 51.0], [7.1, 51.1], [7.0, 51.1], [7.0, 51.0]],
+                    [[7.04, 51.04], [7.06, 51.04], [7.06, 51.06], [7.04, 51.04]]
+                ] } },
+                { "type": "Feature", "properties": {}, "geometry": { "type": "MultiPolygon", "coordinates": [
+                    [[[8.0, 52.0], [8.1, 52.0], [8.1, 52.1], [8.0, 52.0]]],
+                    [[[9.0, 53.0], [9.1, 53.0], [9.1, 53.1], [9.0, 53.0]]]
+                ] } }
+            ]
+        }"#).unwrap();
+
+        let polygons = polygons_of(&value);
+        assert_eq!(3, polygons.len());
+        assert_eq!(1, polygons[0].interiors().len());
+        assert!(polygons[0].contains(&Point::new(7.02, 51.08)));
+        assert!(! polygons[0].contains(&Point::new(7.055, 51.045)));
+    }
+}
@@ -84,15 +84,14 @@ pub fn weave(data: &OsmData) -> Graph {
             }
             
             // Create Edge from WayChunk
-            graph_edges.insert(
+            let mut edge = GraphEdge::new(
                 edge_id,
-                GraphEdge::new(
-                    edge_id,
-                    distance,
-                    is_directed(&way),
-                    chunk.clone() // note: cloning is not very nice
-                )
+                distance,
+                is_directed(&way),
+                chunk.clone() // note: cloning is not very nice
             );
+            edge.set_tags(way.tags.clone());
+            graph_edges.insert(edge_id, edge);
         });
     }
 
@@ -1,20 +1,103 @@
 use geo::Point;
 use geo::Polygon;
+use osmpbfreader::OsmId;
+use osmpbfreader::Tags;
 use osmpbfreader::Node as OsmNode;
 use osmpbfreader::Way as OsmWay;
 use osmpbfreader::Relation as OsmRelation;
 
 use crate::parser::data::*;
 
-// returns some of all interesting points, so route will always different (todo)
+/// An interesting place found in the OpenStreetMap data
+#[derive(Clone)]
+pub struct Poi {
+    /// Location of the place, the center for areas
+    pub point: Point,
+    /// Value of the name tag, if present
+    pub name: Option<String>,
+    /// OpenStreetMap object, the place originates from
+    pub osm_id: OsmId,
+    /// The tag, that makes the place interesting, e.g. "tourism=viewpoint"
+    pub category: String,
+}
+
+impl Poi {
+    fn new(point: Point, tags: &Tags, osm_id: OsmId, k: &str, v: &str) -> Self {
+        Self {
+            point,
+            name: tags.get("name").map(|name| name.to_string()),
+            osm_id,
+            category: format!("{}={}", k, v),
+        }
+    }
+
+    /// Link to the object on openstreetmap.org
+    pub fn url(&self) -> String {
+        let kind = match self.osm_id {
+            OsmId::Node(_) => "node",
+            OsmId::Way(_) => "way",
+            OsmId::Relation(_) => "relation",
+        };
+        format!("https://www.openstreetmap.org/{}/{}", kind, self.osm_id.inner_id())
+    }
+}
+
+/// Every interesting point within reach of a roundtrip of [travel_distance]
+/// kilometers, in random order, so the route differs from run to run
 pub fn interesting_surrounding(
     data: &OsmData,
     start: &Point,
-    travel_distance: &u8
-) -> Vec<Point> {
+    travel_distance: f64
+) -> Vec<Poi> {
     let radius = assumed_radius(travel_distance);
+
+    // remove all points that are too far away
+    // note: include in append steps of interesting_points to save runtime
+    let mut points: Vec<Poi> = interesting_points(data)
+        .into_iter()
+        .filter(|poi| {
+            let distance = geo::HaversineDistance::haversine_distance(start, &poi.point);
+            distance < radius
+        })
+        .collect();
+
+    // to always get a different route, the roundtrip prefers earlier points
+    use rand::thread_rng;
+    use rand::seq::SliceRandom;
+    points.shuffle(&mut thread_rng());
     
-    let mut points: Vec<Point> = Vec::new();
+    points
+}
+
+/// Every interesting point, a trip of [travel_distance] meters from [start]
+/// to [end] can pass by, in random order. The beelines via the point are
+/// not longer than the trip, so they lie in an ellipse around both ends
+pub fn interesting_between(
+    data: &OsmData,
+    start: &Point,
+    end: &Point,
+    travel_distance: f64
+) -> Vec<Poi> {
+    let mut points: Vec<Poi> = interesting_points(data)
+        .into_iter()
+        .filter(|poi| {
+            let via = geo::HaversineDistance::haversine_distance(start, &poi.point)
+                + geo::HaversineDistance::haversine_distance(&poi.point, end);
+            via < travel_distance
+        })
+        .collect();
+
+    // the trip prefers earlier points, just like a roundtrip
+    use rand::thread_rng;
+    use rand::seq::SliceRandom;
+    points.shuffle(&mut thread_rng());
+
+    points
+}
+
+/// every interesting point of the map
+pub fn interesting_points(data: &OsmData) -> Vec<Poi> {
+    let mut points: Vec<Poi> = Vec::new();
 
     points.append(&mut data.nodes
         .iter()
@@ -34,28 +117,11 @@ pub fn interesting_surrounding(
         .collect()
     );
 
-    // remove all points that are too far away
-    // note: include in append steps above to save runtime
-    points = points.into_iter()
-        .filter(|p| {
-            let distance = geo::HaversineDistance::haversine_distance(start, p);
-            distance < radius
-        })
-        .collect();
-    
-
-    // to always get a different route, use only 20 random intersting points
-    use rand::thread_rng;
-    use rand::seq::SliceRandom;
-    points.shuffle(&mut thread_rng());
-    // println!("points: {}", points.len());
-    points.truncate(4 + ((radius * 0.0005) as usize));
-    
     points
 }
 
 // returns a point if it is interesting
-fn interesting_node(node: &OsmNode) -> Option<Point> {
+fn interesting_node(node: &OsmNode) -> Option<Poi> {
     for tag in node.tags.iter() {
         let k = tag.0.as_str();
         let v = tag.1.as_str();
@@ -63,7 +129,7 @@ fn interesting_node(node: &OsmNode) -> Option<Point> {
         if k == "tourism" {
             match v {
                 "viewpoint" | "alpine_hut" | "attraction" | "picnic_site" =>  {
-                    return Some(into_point(&node));
+                    return Some(Poi::new(into_point(&node), &node.tags, OsmId::Node(node.id), k, v));
                 },
                 _ => (),
             }
@@ -72,7 +138,7 @@ fn interesting_node(node: &OsmNode) -> Option<Point> {
             match v {
                 "cairn" | "cross" | "lighthouse" | "mineshaft" | "obelisk" |
                 "observatory" | "watermill" | "windmill" => {
-                    return Some(into_point(&node));
+                    return Some(Poi::new(into_point(&node), &node.tags, OsmId::Node(node.id), k, v));
                 }
                 _ => (),
             }
@@ -84,7 +150,7 @@ fn interesting_node(node: &OsmNode) -> Option<Point> {
                 "castle" | "heritage" | "chruch" | "fort" | "city_gate" |
                 "house" | "wreck" | "cannon" | "aircraft" | "farm" | "tower" |
                 "monastery" | "locomotive" | "ship" | "tank" | "railway_car" => {
-                    return Some(into_point(&node));
+                    return Some(Poi::new(into_point(&node), &node.tags, OsmId::Node(node.id), k, v));
                 },
                 _ => (),
             }
@@ -95,7 +161,7 @@ fn interesting_node(node: &OsmNode) -> Option<Point> {
 }
 
 // returns a point, that is at the center of the way, if it is intersting
-fn interesting_way(data: &OsmData, way: &OsmWay) -> Option<Point> {
+fn interesting_way(data: &OsmData, way: &OsmWay) -> Option<Poi> {
     for tag in way.tags.iter() {
         let k = tag.0.as_str();
         let v = tag.1.as_str();
@@ -106,7 +172,9 @@ fn interesting_way(data: &OsmData, way: &OsmWay) -> Option<Point> {
                 "beach" | "coastline" | "dune"  => {
                     let poly = to_polygon(data, way);
                     let area = area(&poly);
-                    if area > 100.0 { return Some(center(&poly)); }
+                    if area > 100.0 {
+                        return Some(Poi::new(center(&poly), &way.tags, OsmId::Way(way.id), k, v));
+                    }
                 },
                 _ => (),
             }
@@ -117,7 +185,9 @@ fn interesting_way(data: &OsmData, way: &OsmWay) -> Option<Point> {
                 "plant_nursery" | "vineyard" | "grass" => {
                     let poly = to_polygon(data, way);
                     let area = area(&poly);
-                    if area > 100.0 { return Some(center(&poly)); }
+                    if area > 100.0 {
+                        return Some(Poi::new(center(&poly), &way.tags, OsmId::Way(way.id), k, v));
+                    }
                 },
                 _ => (),
             }
@@ -126,7 +196,7 @@ fn interesting_way(data: &OsmData, way: &OsmWay) -> Option<Point> {
             match v {
                 "alpine_hut" | "attraction" | "picnic_site" => {
                     let poly = to_polygon(data, way);
-                    return Some(center(&poly));
+                    return Some(Poi::new(center(&poly), &way.tags, OsmId::Way(way.id), k, v));
                 },
                 _ => (),
             }
@@ -136,7 +206,7 @@ fn interesting_way(data: &OsmData, way: &OsmWay) -> Option<Point> {
                 "cairn" | "obelisk" | "observatory" | "watermill" |
                 "windmill" => {
                     let poly = to_polygon(data, way);
-                    return Some(center(&poly));
+                    return Some(Poi::new(center(&poly), &way.tags, OsmId::Way(way.id), k, v));
                 }
                 _ => (),                
             }
@@ -150,7 +220,7 @@ fn interesting_way(data: &OsmData, way: &OsmWay) -> Option<Point> {
                 "farm" | "tower" | "monastery" | "bridge" | "aqueduct" |
                 "locomotive" | "ship" | "tank" | "railway_car" => {
                     let poly = to_polygon(data, way);
-                    return Some(center(&poly));
+                    return Some(Poi::new(center(&poly), &way.tags, OsmId::Way(way.id), k, v));
                 },
                 _ => (),
             }
@@ -161,7 +231,7 @@ fn interesting_way(data: &OsmData, way: &OsmWay) -> Option<Point> {
 }
 
 // returns a point, that is at the center of all ways, if it is interesting
-fn interesting_relation(data: &OsmData, relation: &OsmRelation) -> Option<Point> {
+fn interesting_relation(data: &OsmData, relation: &OsmRelation) -> Option<Poi> {
     // add heritage
 
     // check only for multipolygons as of now
@@ -178,7 +248,8 @@ fn interesting_relation(data: &OsmData, relation: &OsmRelation) -> Option<Point>
             match v {
                 "water" | "grassland" | "heath" | "wood" | "bay" |
                 "beach" | "coastline" | "dune"  => {
-                    return relation_to_point(data, relation);
+                    return relation_to_point(data, relation)
+                        .map(|p| Poi::new(p, &relation.tags, OsmId::Relation(relation.id), k, v));
                 },
                 _ => (),
             }
@@ -187,7 +258,8 @@ fn interesting_relation(data: &OsmData, relation: &OsmRelation) -> Option<Point>
             match v {
                 "farmland" | "forest" | "flowerbed" | "meadow" | "orchard" |
                 "plant_nursery" | "vineyard" | "grass" => {
-                    return relation_to_point(data, relation);
+                    return relation_to_point(data, relation)
+                        .map(|p| Poi::new(p, &relation.tags, OsmId::Relation(relation.id), k, v));
                 },
                 _ => (),
             }
@@ -249,8 +321,8 @@ fn center(poly: &Polygon) -> Point {
 }
 
 // return radius in meters - distance is kms 
-fn assumed_radius(travel_distance: &u8) -> f64 {
-    *travel_distance as f64 * 1000.0 / 6.28
+fn assumed_radius(travel_distance: f64) -> f64 {
+    travel_distance * 1000.0 / 6.28
 }
 
 fn into_point(node: &OsmNode) -> Point {
@@ -259,6 +331,23 @@ fn into_point(node: &OsmNode) -> Point {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::parser::data_from_pbf;
+
+    #[test]
+    fn viewpoint_is_poi() {
+        let data = data_from_pbf(
+            "resources/dortmund_sued.osm.pbf"
+        );
+
+        // url: https://www.openstreetmap.org/node/283102981
+        let node = data.nodes.get(&283102981).unwrap();
+        let poi = interesting_node(node).unwrap();
+
+        assert_eq!("tourism=viewpoint", poi.category);
+        assert_eq!("https://www.openstreetmap.org/node/283102981", poi.url());
+    }
+
     // use super::{*, viewpoints};
     // use crate::parser::data_from_pbf;
 
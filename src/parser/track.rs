@@ -0,0 +1,53 @@
+use std::{fs::File, io::BufReader};
+
+use geo::Point;
+use gpx::Gpx;
+
+/// Every point of an existing route, e.g. one recorded or planned elsewhere
+pub fn track_from_gpx(path: &str) -> Vec<Point> {
+    let file = File::open(path)
+            .expect("Could not find .gpx file");
+    let gpx = gpx::read(BufReader::new(file))
+            .expect("Could not read .gpx file");
+
+    points_of(&gpx)
+}
+
+/// Points of all tracks, files without tracks fall back to their routes
+fn points_of(gpx: &Gpx) -> Vec<Point> {
+    let track_points: Vec<Point> = gpx.tracks
+        .iter()
+        .flat_map(|track| track.segments.iter())
+        .flat_map(|segment| segment.points.iter())
+        .map(|waypoint| waypoint.point())
+        .collect();
+    if ! track_points.is_empty() { return track_points; }
+
+    gpx.routes
+        .iter()
+        .flat_map(|route| route.points.iter())
+        .map(|waypoint| waypoint.point())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_points_before_route_points() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <rte><rtept lat="51.0" lon="7.0"/></rte>
+  <trk><trkseg>
+    <trkpt lat="51.1" lon="7.1"/>
+    <trkpt lat="51.2" lon="7.2"/>
+  </trkseg></trk>
+</gpx>"#;
+        let mut gpx = gpx::read(xml.as_bytes()).unwrap();
+        assert_eq!(vec![Point::new(7.1, 51.1), Point::new(7.2, 51.2)], points_of(&gpx));
+
+        gpx.tracks.clear();
+        assert_eq!(vec![Point::new(7.0, 51.0)], points_of(&gpx));
+    }
+}
@@ -0,0 +1,58 @@
+use geo::{Centroid, MultiPoint, Point};
+
+use crate::parser::data::*;
+
+/// Place a route has to pass, either a position or an OpenStreetMap object
+#[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Via {
+    Position { lat: f64, lon: f64 },
+    Node { node: NodeId },
+    /// The center of the way, e.g. the outline of a café
+    Way { way: WayId },
+}
+
+impl Via {
+    /// Location of the place, objects have to be in the [data]
+    pub fn point(&self, data: &OsmData) -> Point {
+        let location = |node_id: &NodeId| data.nodes
+            .get(node_id)
+            .map(|node| Point::new(node.lon(), node.lat()))
+            .unwrap_or_else(|| panic!("node {} to pass is not on the map", node_id));
+
+        match self {
+            Self::Position { lat, lon } => Point::new(*lon, *lat),
+            Self::Node { node } => location(node),
+            Self::Way { way } => {
+                let points: MultiPoint = data.ways
+                    .get(way)
+                    .unwrap_or_else(|| panic!("way {} to pass is not on the map", way))
+                    .nodes
+                    .iter()
+                    .map(|node_id| location(&node_id.0.unsigned_abs()))
+                    .collect();
+                points.centroid().expect("way to pass has nodes")
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::data_from_pbf;
+
+    #[test]
+    fn via_from_config() {
+        #[derive(serde::Deserialize)]
+        struct Places { via: Vec<Via> }
+        let places: Places = toml::from_str("via = [{ lat = 51.48, lon = 7.41 }, { node = 283102981 }, { way = 4 }]").unwrap();
+        assert_eq!(vec![Via::Position { lat: 51.48, lon: 7.41 }, Via::Node { node: 283102981 }, Via::Way { way: 4 }], places.via);
+
+        // url: https://www.openstreetmap.org/node/283102981
+        let data = data_from_pbf("resources/dortmund_sued.osm.pbf");
+        let point = places.via[1].point(&data);
+        assert!((point.y() - 51.47).abs() < 0.05 && (point.x() - 7.45).abs() < 0.05);
+        assert_eq!(Point::new(7.41, 51.48), places.via[0].point(&data));
+    }
+}
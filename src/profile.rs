@@ -0,0 +1,53 @@
+/// Kind of bike and rider the route is planned for
+#[derive(serde::Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    /// touring bike with luggage, avoids steep ramps
+    #[default]
+    Trekking,
+    /// light road bike
+    Road,
+    /// mountain bike with low gears
+    Mountain,
+}
+
+impl Profile {
+    pub const ALL: [Profile; 3] = [Self::Trekking, Self::Road, Self::Mountain];
+
+    /// Identical to the name used in the configuration
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Trekking => "trekking",
+            Self::Road => "road",
+            Self::Mountain => "mountain",
+        }
+    }
+
+    /// Steepest average uphill gradient of an edge (height / length) that
+    /// is still considered rideable
+    pub fn max_gradient(&self) -> f64 {
+        match *self {
+            Self::Trekking => 0.10,
+            Self::Road => 0.14,
+            Self::Mountain => 0.20,
+        }
+    }
+
+    /// Average speed on flat ground in meters per second
+    pub fn speed(&self) -> f64 {
+        match *self {
+            Self::Trekking => 16.0 / 3.6,
+            Self::Road => 25.0 / 3.6,
+            Self::Mountain => 14.0 / 3.6,
+        }
+    }
+
+    /// Additional riding time in seconds for every meter of ascent
+    pub fn climb_time(&self) -> f64 {
+        match *self {
+            Self::Trekking => 12.0,
+            Self::Road => 7.0,
+            Self::Mountain => 9.0,
+        }
+    }
+}
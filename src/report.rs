@@ -0,0 +1,268 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use crate::graph::{EdgeId, Graph, NodeId};
+use crate::profile::Profile;
+use crate::router::postprocessor::intermediary_points;
+use crate::router::Path;
+
+/// Statistics of a finished route
+#[derive(serde::Serialize)]
+pub struct Report {
+    /// Total length in meters
+    distance: f64,
+    /// Meters ridden per surface=* value of the ways
+    surfaces: BTreeMap<String, f64>,
+    /// Meters ridden per highway=* value of the ways
+    highways: BTreeMap<String, f64>,
+    /// Share of the distance, that is ridden more than once (0.0 till 1.0)
+    repeated: f64,
+    /// Count of interesting points the route passes
+    pois: usize,
+    /// Estimated riding time in seconds for every profile
+    ride_time: BTreeMap<String, f64>,
+    /// Height gain in meters, if elevation data is available
+    ascent: Option<f64>,
+    /// Height loss in meters, if elevation data is available
+    descent: Option<f64>,
+    /// Meters between the configured start and the route
+    snapping: Option<f64>,
+    /// Intersections dropped from the network, because they are cut off
+    /// from the rest of it
+    islands: usize,
+    /// Roads removed from the network, because they enter an area to avoid
+    avoided: usize,
+    /// Wishes, the route does not fulfill
+    warnings: Vec<String>,
+}
+
+impl Report {
+    /// Analyze the [route], [pois] are the nodes the route was planned to visit
+    pub fn new(graph: &Graph, route: &Path, pois: &[NodeId]) -> Self {
+        let mut distance = 0.0;
+        let mut repeated = 0.0;
+        let mut surfaces: BTreeMap<String, f64> = BTreeMap::new();
+        let mut highways: BTreeMap<String, f64> = BTreeMap::new();
+        // how often every edge has been ridden so far
+        let mut ridden: HashMap<EdgeId, usize> = HashMap::new();
+
+        let mut heights: Vec<Option<f64>> = Vec::new();
+        if let Some(first) = route.nodes().first() {
+            heights.push(*graph.nodes().get(first).unwrap().elevation());
+        }
+
+        for (pair, edge_id) in route.nodes().windows(2).zip(route.edges()) {
+            let mut nodes = vec![pair[0]];
+            nodes.append(&mut intermediary_points(graph, edge_id, &pair[0], &pair[1]));
+            nodes.push(pair[1]);
+
+            let length = length(graph, &nodes);
+            heights.extend(nodes
+                .iter()
+                .skip(1)
+                .map(|node_id| *graph.nodes().get(node_id).unwrap().elevation())
+            );
+
+            let edge = graph.edges().get(edge_id).unwrap();
+
+            let count = ridden.entry(*edge_id).or_insert(0);
+            *count += 1;
+            if *count > 1 { repeated += length; }
+
+            *surfaces.entry(tag_value(edge.tags(), "surface")).or_insert(0.0) += length;
+            *highways.entry(tag_value(edge.tags(), "highway")).or_insert(0.0) += length;
+            distance += length;
+        }
+
+        let (ascent, descent) = match heights.iter().any(|h| h.is_some()) {
+            true => {
+                let (a, d) = crate::elevation::climb(&heights);
+                (Some(a), Some(d))
+            },
+            false => (None, None),
+        };
+
+        let passed: HashSet<&NodeId> = route.nodes().iter().collect();
+        let ride_time = Profile::ALL
+            .iter()
+            .map(|profile| (
+                profile.name().to_string(),
+                ride_time(profile, distance, ascent.unwrap_or(0.0))
+            ))
+            .collect();
+
+        Self {
+            distance,
+            surfaces,
+            highways,
+            repeated: if distance > 0.0 { repeated / distance } else { 0.0 },
+            pois: pois.iter().filter(|poi| passed.contains(poi)).count(),
+            ride_time,
+            ascent,
+            descent,
+            snapping: None,
+            islands: 0,
+            avoided: 0,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Share of the distance, that is ridden more than once
+    pub fn repeated(&self) -> f64 {
+        self.repeated
+    }
+
+    pub fn set_snapping(&mut self, meters: f64) {
+        self.snapping = Some(meters);
+    }
+
+    pub fn set_islands(&mut self, intersections: usize) {
+        self.islands = intersections;
+    }
+
+    pub fn set_avoided(&mut self, roads: usize) {
+        self.avoided = roads;
+    }
+
+    pub fn add_warning(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    /// Machine readable version of the report
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// The most important values in one line, e.g. for a gpx description
+    pub fn summary(&self, profile: &Profile) -> String {
+        let mut summary = format!("{:.1} km", self.distance / 1000.0);
+        if let Some(ascent) = self.ascent {
+            summary.push_str(&format!(", {:.0} m ascent", ascent));
+        }
+        summary.push_str(&format!(", {} pois", self.pois));
+        summary.push_str(&format!(
+            ", {} by {} bike",
+            duration(self.ride_time[profile.name()]),
+            profile.name()
+        ));
+        summary
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "distance:  {:.2} km", self.distance / 1000.0)?;
+        if let (Some(ascent), Some(descent)) = (self.ascent, self.descent) {
+            writeln!(f, "elevation: {:.0} m up, {:.0} m down", ascent, descent)?;
+        }
+        writeln!(f, "repeated:  {:.1} %", self.repeated * 100.0)?;
+        writeln!(f, "pois:      {}", self.pois)?;
+        if let Some(snapping) = self.snapping {
+            writeln!(f, "snapping:  {:.0} m to the start", snapping)?;
+        }
+        if self.islands > 0 {
+            writeln!(f, "islands:   {} intersections dropped", self.islands)?;
+        }
+        if self.avoided > 0 {
+            writeln!(f, "avoided:   {} roads in areas to avoid", self.avoided)?;
+        }
+        for warning in self.warnings.iter() {
+            writeln!(f, "warning:   {}", warning)?;
+        }
+
+        writeln!(f, "ride time:")?;
+        for (profile, seconds) in self.ride_time.iter() {
+            writeln!(f, "    {:<18} {}", profile, duration(*seconds))?;
+        }
+        writeln!(f, "surfaces:")?;
+        for (surface, meters) in self.surfaces.iter() {
+            writeln!(f, "    {:<18} {:.2} km", surface, meters / 1000.0)?;
+        }
+        writeln!(f, "highways:")?;
+        for (highway, meters) in self.highways.iter() {
+            writeln!(f, "    {:<18} {:.2} km", highway, meters / 1000.0)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Length in meters along the nodes
+fn length(graph: &Graph, nodes: &[NodeId]) -> f64 {
+    nodes
+        .windows(2)
+        .map(|pair| geo::HaversineDistance::haversine_distance(
+            graph.nodes().get(&pair[0]).unwrap().point(),
+            graph.nodes().get(&pair[1]).unwrap().point()
+        ))
+        .sum()
+}
+
+/// value of the tag with key [k] or "unknown" if the tag is missing
+fn tag_value(tags: &osmpbfreader::Tags, k: &str) -> String {
+    match tags.get(k) {
+        Some(v) => v.to_string(),
+        None => String::from("unknown"),
+    }
+}
+
+/// riding time in seconds with [profile] for the [distance] and [ascent] in meters
+fn ride_time(profile: &Profile, distance: f64, ascent: f64) -> f64 {
+    distance / profile.speed() + ascent * profile.climb_time()
+}
+
+/// seconds as hours and minutes, e.g. "2:05 h"
+fn duration(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).round() as u64;
+    format!("{}:{:02} h", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::Point;
+    use crate::graph::{Node as GraphNode, Edge as GraphEdge};
+
+    fn tags(pairs: &[(&str, &str)]) -> osmpbfreader::Tags {
+        let mut tags = osmpbfreader::Tags::new();
+        for (k, v) in pairs {
+            tags.insert(k.to_string().into(), v.to_string().into());
+        }
+        tags
+    }
+
+    #[test]
+    fn report_out_and_back() {
+        // 0<-->1<-->2, ridden 0 -> 1 -> 2 -> 1
+        let mut graph_nodes: HashMap<NodeId, GraphNode> = HashMap::new();
+        graph_nodes.insert(0, GraphNode::new(0, Point::new(7.0, 51.0)));
+        graph_nodes.insert(1, GraphNode::new(1, Point::new(7.01, 51.0)));
+        graph_nodes.insert(2, GraphNode::new(2, Point::new(7.02, 51.0)));
+
+        let mut graph_edges: HashMap<EdgeId, GraphEdge> = HashMap::new();
+        let mut asphalt = GraphEdge::new(0, 1.0, false, vec![0, 1]);
+        asphalt.set_tags(tags(&[("highway", "residential"), ("surface", "asphalt")]));
+        graph_edges.insert(0, asphalt);
+        graph_nodes.get_mut(&0).unwrap().insert_edge(0);
+        graph_nodes.get_mut(&1).unwrap().insert_edge(0);
+        let mut track = GraphEdge::new(1, 1.0, false, vec![1, 2]);
+        track.set_tags(tags(&[("highway", "track")]));
+        graph_edges.insert(1, track);
+        graph_nodes.get_mut(&1).unwrap().insert_edge(1);
+        graph_nodes.get_mut(&2).unwrap().insert_edge(1);
+
+        let graph = Graph::new(graph_nodes, graph_edges);
+        let report = Report::new(&graph, &Path::new(vec![0, 1, 2, 1], vec![0, 1, 1]), &[2, 5]);
+
+        let leg = geo::HaversineDistance::haversine_distance(
+            &Point::new(7.0, 51.0),
+            &Point::new(7.01, 51.0)
+        );
+        assert!((report.distance - 3.0 * leg).abs() < 0.01);
+        assert!((report.repeated - 1.0 / 3.0).abs() < 0.01);
+        assert!((report.surfaces["unknown"] - 2.0 * leg).abs() < 0.01);
+        assert!((report.highways["residential"] - leg).abs() < 0.01);
+        assert_eq!(1, report.pois);
+        assert_eq!(None, report.ascent);
+    }
+}
@@ -5,65 +5,176 @@ use geo::Point;
 use ordered_float::NotNan;
 use priority_queue::PriorityQueue;
 
-use crate::graph::{Graph, NodeId};
+use crate::graph::{EdgeId, Graph, NodeId, Edge as GraphEdge};
 
+mod matcher;
+mod orienteering;
 mod preprocessor;
 pub mod postprocessor;
+mod quality;
+mod roundtrip;
+mod shape;
+mod weighting;
+
+pub use matcher::{match_track, passed_by};
+pub use orienteering::orienteering;
+pub use preprocessor::{concave_hull_of, Direction};
+pub use quality::repair;
+pub use roundtrip::roundtrip;
+pub use shape::{shaped, Outline, Shape};
+pub use weighting::Weighting;
+use weighting::Overlap;
+
+/// How the pois to visit are chosen and ordered
+#[derive(serde::Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    /// A few random pois in the order of their concave hull, the length of
+    /// the route is left to chance
+    Unoptimized,
+    /// Pois are added and removed until the length fits, see [roundtrip]
+    #[default]
+    Insertion,
+    /// As many pois as the length allows, see [orienteering]
+    Orienteering,
+}
+
+/// Intersections, a planned route begins at, has to pass and ends at. The
+/// end is the start again for a loop
+pub struct Stops {
+    pub start: NodeId,
+    /// passed in any case, in the order that fits best
+    pub via: Vec<NodeId>,
+    pub end: NodeId,
+}
+
+/// Route of a planner and how far it fulfills the wishes
+#[derive(Default)]
+pub struct Plan {
+    pub route: Path,
+    /// whether the route is as long as the window asks for
+    pub fits: bool,
+    /// waypoints, that can not be reached and are left out
+    pub skipped: Vec<NodeId>,
+}
+
+/// Intersections of a route together with the edges connecting them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+    nodes: Vec<NodeId>,
+    /// edges[i] leads from nodes[i] to nodes[i + 1]
+    edges: Vec<EdgeId>,
+}
+
+impl Path {
+    /// The [edges] connect the consecutive [nodes]
+    pub fn new(nodes: Vec<NodeId>, edges: Vec<EdgeId>) -> Self {
+        assert_eq!(nodes.len(), edges.len() + 1, "every edge needs two nodes");
+        Self { nodes, edges }
+    }
+
+    pub fn nodes(&self) -> &Vec<NodeId> {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &Vec<EdgeId> {
+        &self.edges
+    }
+
+    /// Ridden meters along all edges
+    pub fn length(&self, graph: &Graph) -> f64 {
+        self.edges.iter().map(|edge_id| graph.edges().get(edge_id).unwrap().distance()).sum()
+    }
+
+    /// Continue the path with [other], which has to begin at the last node
+    pub fn append(&mut self, other: &mut Path) {
+        if other.nodes.first() == self.nodes.last() { other.nodes.remove(0); }
+        self.nodes.append(&mut other.nodes);
+        self.edges.append(&mut other.edges);
+    }
+}
 
 /// note: the result yields only contains only starts and ends of ways (intersections)
-///       an accurate trace has to be calculated later on
-/// note: add functionality to use ways twice only in utmost demand
-pub fn unoptimized(graph: &Graph, visit: &mut Vec<NodeId>, start: &NodeId) -> Vec<NodeId> {
-    let mut route: Vec<NodeId> = Vec::new();
-    // the route begins with the start node
-    route.push(*start);
-    
+///       an accurate trace has to be calculated later on. Nothing is retried,
+///       if the route does not fit into the [window] (min, max) in meters
+pub fn unoptimized(
+    graph: &Graph,
+    weighting: &Weighting,
+    visit: &mut Vec<NodeId>,
+    stops: &Stops,
+    window: (f64, f64),
+    direction: &Direction
+) -> Plan {
+    // the via points are ordered just like the pois
+    visit.splice(0..0, stops.via.iter().copied());
     // note: start could be a node in the middle of a way, breaks assumption that
     // only way's s and t are included here, but a_star will handle this
     // by defaulting into one direction
-    let mut visit = preprocessor::order_with_concave_hull(graph, start, visit);
-    // the last node to visit is the start node
-    // note: if is_roundtrip ...
-    visit.push(*start);
-
-    // add the last few nodes to a blacklist,
-    // to avoid walking paths twice / backwards (if possible)
-    let mut blacklist: HashSet<NodeId> = HashSet::new();
-    let no_blacklist: HashSet<NodeId> = HashSet::new();
-    
-    // note: at the moment in between nodes of a way are not contained here
+    let visit = match stops.start == stops.end {
+        true => preprocessor::order_with_concave_hull(graph, &stops.start, visit, direction),
+        false => preprocessor::order_along(graph, &stops.start, &stops.end, visit),
+    };
+    let (route, skipped) = route_through(graph, weighting, &visit, &stops.start, &stops.end);
+    let fits = (window.0..=window.1).contains(&route.length(graph));
+    Plan { route, fits, skipped }
+}
+
+/// Ride from [start] through the [waypoints] in their order to [end], which
+/// is [start] again for a roundtrip. Edges of previous parts are avoided, if
+/// there is a reasonable alternative. Waypoints, that can not be reached,
+/// are skipped and returned as well
+fn route_through(
+    graph: &Graph,
+    weighting: &Weighting,
+    waypoints: &[NodeId],
+    start: &NodeId,
+    end: &NodeId
+) -> (Path, Vec<NodeId>) {
+    // the route begins with the start node
+    let mut route = Path::new(vec![*start], Vec::new());
+
+    // the last node to visit is the end node
+    let mut visit = waypoints.to_vec();
+    visit.push(*end);
+
+    // roads ridden so far are avoided, especially in the opposite direction
+    let mut overlap = Overlap::new();
+    let mut skipped = Vec::new();
+
     while ! visit.is_empty() {
-        let from = route.last().unwrap();
+        let from = route.nodes().last().unwrap();
         let to = visit.remove(0);
-        
-        let mut part;
-        match a_star(graph, &blacklist, from, &to) {
-            Some(p) => part = p,
-            None => part = a_star(graph, &no_blacklist, from, &to).unwrap_or(Vec::new()),
-        }
-        if part.len() > 0 { part.remove(0); }
-        
-        blacklist.extend(part.iter().copied());
-        
+
+        // retry without the penalties of the previous parts, before the
+        // waypoint is skipped. The component ignores too steep edges
+        let found = a_star(graph, weighting, &overlap, from, &to)
+            .or_else(|| a_star(graph, weighting, &Overlap::new(), from, &to));
+        let mut part = match found {
+            Some(part) => part,
+            None => {
+                skipped.push(to);
+                continue;
+            },
+        };
+        overlap.add(graph, &part);
+
         route.append(&mut part);
     }
 
-    route
+    (route, skipped)
 }
 
-
-/// returns empty vector if no path exists between the two nodes
-/// note: is it really useful to return nodes? - the edges contain all location
-/// data, that has to be reconstructed later on
-
+/// returns None if no path exists between the two nodes, the path contains
+/// the edges it follows, so the geometry can be reconstructed later on
 fn a_star(
     graph: &Graph,
-    blacklist: &HashSet<NodeId>,
+    weighting: &Weighting,
+    overlap: &Overlap,
     start: &NodeId,
     end: &NodeId
-) -> Option<Vec<NodeId>> {
-    // key == node, value == predecessor
-    let mut came_from: HashMap<NodeId, NodeId> = HashMap::new();
+) -> Option<Path> {
+    // key == node, value == predecessor and the edge leading from it
+    let mut came_from: HashMap<NodeId, (NodeId, EdgeId)> = HashMap::new();
 
     // least known distance from start to key
     let mut g_score: HashMap<NodeId, f64> = HashMap::new();
@@ -71,7 +182,8 @@ fn a_star(
 
     // heuristic of distance from start node via=key to end node
     let mut f_score: HashMap<NodeId, f64> = HashMap::new();
-    let h = heuristic_distance(graph, start, end);
+    // the beeline is scaled, so the estimate is never above the real costs
+    let h = heuristic_distance(graph, start, end) * weighting.min_cost_factor();
     f_score.insert(*start, h); // start via start to end
 
     let mut open_set: PriorityQueue<NodeId, Reverse<NotNan<f64>>> = PriorityQueue::new();
@@ -82,14 +194,15 @@ fn a_star(
         let node_id = current.0;
         if node_id == *end {
             // collect path from start to end
-            let mut path: Vec<NodeId> = Vec::new();
+            let mut path = Path::new(vec![current.0], Vec::new());
             let mut current: NodeId = current.0;
-            path.push(current);
-            while came_from.contains_key(&current) {
-                current = *came_from.get(&current).unwrap();
-                path.push(current);
+            while let Some((previous, edge_id)) = came_from.get(&current) {
+                current = *previous;
+                path.nodes.push(current);
+                path.edges.push(*edge_id);
             }
-            path.reverse();
+            path.nodes.reverse();
+            path.edges.reverse();
             return Some(path);
         }
         let node = graph.nodes().get(&node_id).unwrap();
@@ -97,29 +210,21 @@ fn a_star(
         for edge_id in node.edges() {
             // find other end of edge
             let edge = graph.edges().get(&edge_id).unwrap();
-            let neighbour_node_id: NodeId;
-
-            if *edge.t() == node_id && *edge.directed() == false {
-                // go to the beginning of the edge
-                neighbour_node_id = *edge.s();
-            } else {
-                // go to the end of the edge
-                neighbour_node_id = *edge.t();
-            }
+            let (neighbour_node_id, forward) = other_end(edge, &node_id);
 
-            // blacklisted nodes are not to be visited
-            if blacklist.contains(&neighbour_node_id) {
-                // already in set => node has been explicitly excluded
-                continue;
-            }
+            // too steep edges can not be ridden in this direction
+            let cost = match weighting.cost(edge, forward) {
+                Some(c) => c * overlap.factor(edge_id, forward),
+                None => continue,
+            };
 
-            let tentative_g_score: f64 = g_score.get(&node_id).unwrap() + edge.distance();
+            let tentative_g_score: f64 = g_score.get(&node_id).unwrap() + cost;
 
             if tentative_g_score < *g_score.get(&neighbour_node_id).unwrap_or(&f64::MAX) {
-                came_from.insert(neighbour_node_id, node_id);
+                came_from.insert(neighbour_node_id, (node_id, *edge_id));
                 g_score.insert(neighbour_node_id, tentative_g_score);
                 
-                let h = heuristic_distance(graph, &neighbour_node_id, end);
+                let h = heuristic_distance(graph, &neighbour_node_id, end) * weighting.min_cost_factor();
                 
                 let f = tentative_g_score + h; 
                 f_score.insert(neighbour_node_id, f);
@@ -132,6 +237,18 @@ fn a_star(
     None
 }
 
+/// The node reached by riding along [edge] from [node_id] and whether this
+/// follows the direction of the edge
+fn other_end(edge: &GraphEdge, node_id: &NodeId) -> (NodeId, bool) {
+    if edge.t() == node_id && *edge.directed() == false {
+        // go to the beginning of the edge
+        (*edge.s(), false)
+    } else {
+        // go to the end of the edge
+        (*edge.t(), true)
+    }
+}
+
 fn heuristic_distance(graph: &Graph, from: &NodeId, to: &NodeId) -> f64 {
     geo::HaversineDistance::haversine_distance(
         graph.nodes().get(from).unwrap().point(),
@@ -139,49 +256,29 @@ fn heuristic_distance(graph: &Graph, from: &NodeId, to: &NodeId) -> f64 {
     )
 }
 
-/// Find nearest point that is in the road network
-pub fn closest_point(graph: &Graph, p: &Point) -> NodeId {
-    struct Candidate {
-        node_id: Option<NodeId>,
-        distance: f64
-    }
-
-    let mut candidate = Candidate { node_id: None, distance: f64::MAX };
-    for node in graph.nodes() {
-        // maybe use harvesine distance instead of euclidean
-        let current_distance = geo::HaversineDistance::haversine_distance(p, node.1.point());
-        if candidate.distance > current_distance {
-            candidate.node_id = Some(*node.0);
-            candidate.distance = current_distance;
-        }
-    }
-    candidate.node_id.unwrap()
+/// Snap [p] to the closest position on an edge and split the edge there.
+/// Returns the node at that position and its distance to [p] in meters
+pub fn snap(graph: &mut Graph, p: &Point) -> (NodeId, f64) {
+    let nearest = graph.index().nearest_edge(p).expect("graph has no edges");
+    (graph.split_edge(&nearest), nearest.distance)
 }
 
-/// Find nearest intersection that is in the road network
-pub fn closest_intersection(graph: &Graph, p: &Point) -> NodeId {
-    struct Candidate {
-        node_id: Option<NodeId>,
-        distance: f64
-    }
-
-    let mut candidate = Candidate { node_id: None, distance: f64::MAX };
-    for node in graph.nodes() {
-        if node.1.edges().len() < 2 { continue; } // not an intersection
-        // maybe use harvesine distance instead of euclidean
-        let current_distance = geo::HaversineDistance::haversine_distance(p, node.1.point());
-        if candidate.distance > current_distance {
-            candidate.node_id = Some(*node.0);
-            candidate.distance = current_distance;
-        }
-    }
-    candidate.node_id.unwrap()
+/// Find nearest intersection that is in the road network and one of the
+/// [reachable] ones, so that the route can get there and back to the start.
+/// Fails, if the start is cut off from every intersection
+pub fn closest_intersection(graph: &Graph, p: &Point, reachable: &HashSet<NodeId>) -> Result<NodeId, String> {
+    graph.index()
+        .nearest_nodes(p)
+        .filter(|node_id| reachable.contains(node_id))
+        // not an intersection
+        .find(|node_id| graph.nodes().get(node_id).unwrap().edges().len() >= 2)
+        .ok_or_else(|| String::from("no intersection can be reached from the start"))
 }
 
-pub fn nearest_graph_nodes(graph: &Graph, points: &Vec<Point>) -> Vec<NodeId> {
-    let ids: Vec<NodeId> = points
+pub fn nearest_graph_nodes(graph: &Graph, points: &Vec<Point>, reachable: &HashSet<NodeId>) -> Result<Vec<NodeId>, String> {
+    let ids: Result<Vec<NodeId>, String> = points
         .iter()
-        .map(|p| closest_intersection(&graph, &p))
+        .map(|p| closest_intersection(graph, p, reachable))
         .collect();
     ids
 }
@@ -214,11 +311,20 @@ mod tests {
 
         let graph = Graph::new(graph_nodes, graph_edges);
 
-        let result = a_star(&graph, &mut HashSet::new(), &1, &0);
+        let result = a_star(&graph, &Weighting::flat(), &Overlap::new(), &1, &0);
         assert_eq!(None, result);
 
-        let result = a_star(&graph, &mut HashSet::new(), &2, &0);
+        let result = a_star(&graph, &Weighting::flat(), &Overlap::new(), &2, &0);
         assert_eq!(None, result);
+
+        // the unreachable waypoint is left out
+        let (route, skipped) = route_through(&graph, &Weighting::flat(), &[0], &1, &2);
+        assert_eq!(&vec![1, 2], route.nodes());
+        assert_eq!(vec![0], skipped);
+
+        // 1 is the only intersection, 0 and 2 are dead ends
+        assert_eq!(Ok(1), closest_intersection(&graph, &Point::new(0.0, 0.0), &HashSet::from([0, 1, 2])));
+        assert!(closest_intersection(&graph, &Point::new(0.0, 0.0), &HashSet::from([0, 2])).is_err());
     }
 
     #[test]
@@ -276,9 +382,120 @@ mod tests {
 
         let graph = Graph::new(graph_nodes, graph_edges);
         
-        let result = a_star(&graph, &mut HashSet::new(), &0, &6).unwrap();
+        let result = a_star(&graph, &Weighting::flat(), &Overlap::new(), &0, &6).unwrap();
         let should_be = vec![0, 1, 2, 3, 7, 6];
 
-        assert_eq!(should_be, result);
+        assert_eq!(&should_be, result.nodes());
+        assert_eq!(&vec![0, 1, 2, 6, 9], result.edges());
+    }
+
+    #[test]
+    fn a_star_avoids_climb() {
+        // start: 0
+        // end: 1
+        //
+        // 0<-->1  (uphill from 0 to 1)
+        //  \  /
+        //   2
+
+        let mut graph_nodes: HashMap<NodeId, GraphNode> = HashMap::new();
+        graph_nodes.insert(0, GraphNode::new(0, Point::new(0.0, 0.0)));
+        graph_nodes.insert(1, GraphNode::new(1, Point::new(0.000005, 0.0)));
+        graph_nodes.insert(2, GraphNode::new(2, Point::new(0.0000025, -0.000005)));
+
+        let mut graph_edges: HashMap<EdgeId, GraphEdge> = HashMap::new();
+        let mut climb = GraphEdge::new(0, 1.0, false, vec![0, 1]);
+        climb.set_climb(0.05, 0.0);
+        graph_edges.insert(0, climb);
+        graph_nodes.get_mut(&0).unwrap().insert_edge(0);
+        graph_nodes.get_mut(&1).unwrap().insert_edge(0);
+        graph_edges.insert(1, GraphEdge::new(1, 1.0, false, vec![0, 2]));
+        graph_nodes.get_mut(&0).unwrap().insert_edge(1);
+        graph_nodes.get_mut(&2).unwrap().insert_edge(1);
+        graph_edges.insert(2, GraphEdge::new(2, 1.0, false, vec![2, 1]));
+        graph_nodes.get_mut(&2).unwrap().insert_edge(2);
+        graph_nodes.get_mut(&1).unwrap().insert_edge(2);
+
+        let graph = Graph::new(graph_nodes, graph_edges);
+
+        let result = a_star(&graph, &Weighting::flat(), &Overlap::new(), &0, &1).unwrap();
+        assert_eq!(&vec![0, 1], result.nodes());
+
+        let weighting = Weighting::new(&crate::profile::Profile::Road, 100.0);
+        let result = a_star(&graph, &weighting, &Overlap::new(), &0, &1).unwrap();
+        assert_eq!(&vec![0, 2, 1], result.nodes());
+        assert_eq!(&vec![1, 2], result.edges());
+
+        // downhill the direct way is still the best
+        let result = a_star(&graph, &weighting, &Overlap::new(), &1, &0).unwrap();
+        assert_eq!(&vec![1, 0], result.nodes());
+    }
+
+    #[test]
+    fn snap_splits_edge() {
+        // 0<-->1<-->2, snapped between 1 and 2
+        let mut graph_nodes: HashMap<NodeId, GraphNode> = HashMap::new();
+        graph_nodes.insert(0, GraphNode::new(0, Point::new(7.000, 51.0)));
+        graph_nodes.insert(1, GraphNode::new(1, Point::new(7.001, 51.0)));
+        graph_nodes.insert(2, GraphNode::new(2, Point::new(7.002, 51.0)));
+        let mut graph_edges: HashMap<EdgeId, GraphEdge> = HashMap::new();
+        graph_edges.insert(5, GraphEdge::new(5, 140.0, false, vec![0, 1, 2]));
+        for node_id in 0..3 { graph_nodes.get_mut(&node_id).unwrap().insert_edge(5); }
+        let mut graph = Graph::new(graph_nodes, graph_edges);
+
+        let (start, distance) = snap(&mut graph, &Point::new(7.0015, 51.0001));
+        assert_eq!(VIRTUAL_NODE, start);
+        assert!((distance - 11.1).abs() < 0.5);
+        assert_eq!(&Point::new(7.0015, 51.0), graph.nodes().get(&start).unwrap().point());
+
+        let first = (2046 << 53) | 5;
+        let second = (2047 << 53) | 5;
+        assert_eq!(&vec![0, 1, VIRTUAL_NODE], graph.edges().get(&first).unwrap().nodes());
+        assert_eq!(&vec![VIRTUAL_NODE, 2], graph.edges().get(&second).unwrap().nodes());
+        // the halves replace the split edge, also in the index
+        assert!(! graph.edges().contains_key(&5));
+        assert_eq!(&vec![first], graph.nodes().get(&1).unwrap().edges());
+        assert_eq!(&vec![second], graph.nodes().get(&2).unwrap().edges());
+        assert_eq!(second, graph.index().nearest_edge(&Point::new(7.0018, 51.0001)).unwrap().edge_id);
+
+        // the route begins and ends exactly at the snapped position
+        let there = a_star(&graph, &Weighting::flat(), &Overlap::new(), &start, &0).unwrap();
+        assert_eq!(&vec![first], there.edges());
+        let back = a_star(&graph, &Weighting::flat(), &Overlap::new(), &0, &start).unwrap();
+        let trip = postprocessor::Trip::new(&graph, &back, Vec::new());
+        assert_eq!(3, trip.points().len());
+        assert!((trip.distance() - 105.0).abs() < 1.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn a_star_parallel_edges() {
+        // two ways connect 0 and 1, the detour via 2 is longer
+        //
+        //   2
+        //  / \
+        // 0---1
+
+        let mut graph_nodes: HashMap<NodeId, GraphNode> = HashMap::new();
+        graph_nodes.insert(0, GraphNode::new(0, Point::new(7.000, 51.0)));
+        graph_nodes.insert(1, GraphNode::new(1, Point::new(7.002, 51.0)));
+        graph_nodes.insert(2, GraphNode::new(2, Point::new(7.001, 51.001)));
+
+        let mut graph_edges: HashMap<EdgeId, GraphEdge> = HashMap::new();
+        graph_edges.insert(0, GraphEdge::new(0, 3.0, false, vec![0, 2, 1]));
+        graph_edges.insert(1, GraphEdge::new(1, 2.0, false, vec![0, 1]));
+        for edge_id in [0, 1] {
+            graph_nodes.get_mut(&0).unwrap().insert_edge(edge_id);
+            graph_nodes.get_mut(&1).unwrap().insert_edge(edge_id);
+        }
+
+        let graph = Graph::new(graph_nodes, graph_edges);
+
+        let result = a_star(&graph, &Weighting::flat(), &Overlap::new(), &0, &1).unwrap();
+        assert_eq!(&vec![0, 1], result.nodes());
+        assert_eq!(&vec![1], result.edges());
+
+        // the geometry follows the chosen edge, not the detour
+        let trip = postprocessor::Trip::new(&graph, &result, Vec::new());
+        assert_eq!(2, trip.points().len());
+    }
+}
@@ -0,0 +1,354 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use geo::{HaversineDistance, Point};
+use ordered_float::NotNan;
+use priority_queue::PriorityQueue;
+
+use crate::graph::{EdgeId, EdgeMatch, Graph, NodeId};
+use crate::router::{a_star, other_end, Path, Weighting};
+use crate::router::weighting::Overlap;
+
+/// Standard deviation of gps fixes in meters
+const GPS_SIGMA: f64 = 10.0;
+/// Typical difference in meters between the ridden distance and the air line
+/// between two fixes, larger differences are exponentially less likely
+const ROUTE_BETA: f64 = 20.0;
+/// Edges further away from a fix are no candidates
+const SEARCH_RADIUS: f64 = 50.0;
+/// Only the nearest edges of every fix are considered
+const MAX_CANDIDATES: usize = 8;
+
+/// Distance along the graph to a node and the step leading there
+type Reachable = HashMap<NodeId, (f64, Option<(NodeId, EdgeId)>)>;
+
+/// Snap a recorded [track] onto the graph, see [match_edges]. Fails, if no
+/// fix is close to a road
+pub fn match_track(graph: &Graph, track: &[Point]) -> Result<Path, String> {
+    let edges = match_edges(graph, track);
+    if edges.is_empty() { return Err(String::from("track does not follow any road of the map")); }
+    Ok(to_path(graph, &edges))
+}
+
+/// The sequence of edges most likely ridden while recording the noisy gps
+/// [points]. Hidden markov model solved with the Viterbi algorithm: the
+/// candidates of a fix are the edges close to it, transitions are likely,
+/// if the distance along the graph is similar to the air line
+pub fn match_edges(graph: &Graph, points: &[Point]) -> Vec<EdgeId> {
+    // fixes without any edge close by can not be matched
+    let layers: Vec<(Point, Vec<EdgeMatch>)> = points
+        .iter()
+        .map(|p| {
+            let mut candidates = graph.index().edges_within(p, SEARCH_RADIUS);
+            candidates.truncate(MAX_CANDIDATES);
+            (*p, candidates)
+        })
+        .filter(|(_, candidates)| ! candidates.is_empty())
+        .collect();
+    if layers.is_empty() { return Vec::new(); }
+
+    // log probabilities of the best sequence ending in every candidate
+    let mut scores: Vec<f64> = layers[0].1.iter().map(emission).collect();
+    // best predecessor of every candidate, None if the sequence restarts
+    let mut came_from: Vec<Vec<Option<usize>>> = vec![vec![None; scores.len()]];
+
+    for t in 1..layers.len() {
+        let (previous_point, previous) = &layers[t - 1];
+        let (point, current) = &layers[t];
+        let air = previous_point.haversine_distance(point);
+
+        let mut next_scores = vec![f64::NEG_INFINITY; current.len()];
+        let mut next_came_from = vec![None; current.len()];
+        for (a, from) in previous.iter().enumerate() {
+            if scores[a] == f64::NEG_INFINITY { continue; }
+            let reachable = reach(graph, from, max_route(air));
+
+            for (b, to) in current.iter().enumerate() {
+                let route = match route_distance(graph, &reachable, from, to) {
+                    Some((r, _)) => r,
+                    None => continue,
+                };
+                let score = scores[a] - (route - air).abs() / ROUTE_BETA + emission(to);
+                if score > next_scores[b] {
+                    next_scores[b] = score;
+                    next_came_from[b] = Some(a);
+                }
+            }
+        }
+
+        // no candidate is reachable, the recording has a gap
+        if next_scores.iter().all(|s| *s == f64::NEG_INFINITY) {
+            next_scores = current.iter().map(emission).collect();
+        }
+        scores = next_scores;
+        came_from.push(next_came_from);
+    }
+
+    // follow the best sequence backwards
+    let mut chosen: Vec<EdgeMatch> = Vec::new();
+    let mut best = (0..scores.len())
+        .max_by(|a, b| scores[*a].total_cmp(&scores[*b]))
+        .unwrap();
+    for t in (0..layers.len()).rev() {
+        chosen.push(layers[t].1[best]);
+        if t == 0 { break; }
+        // after a gap continue with the closest candidate, they are sorted
+        best = came_from[t][best].unwrap_or(0);
+    }
+    chosen.reverse();
+
+    // connect the chosen edges along the graph
+    let mut edges: Vec<EdgeId> = vec![chosen[0].edge_id];
+    for pair in chosen.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        if from.edge_id == to.edge_id { continue; }
+
+        let air = from.projection.haversine_distance(&to.projection);
+        let reachable = reach(graph, from, max_route(air));
+        if let Some((_, entry)) = route_distance(graph, &reachable, from, to) {
+            edges.append(&mut edges_to(&reachable, &entry));
+        }
+        edges.push(to.edge_id);
+        edges.dedup();
+    }
+
+    edges
+}
+
+/// Logarithmic likelihood, that the fix was recorded on the candidate edge
+fn emission(candidate: &EdgeMatch) -> f64 {
+    -0.5 * (candidate.distance / GPS_SIGMA).powi(2)
+}
+
+/// Longest distance along the graph worth searching between two fixes
+fn max_route(air: f64) -> f64 {
+    2.0 * air + 2.0 * SEARCH_RADIUS
+}
+
+/// Shortest distances from the position of [from] to every node within
+/// [bound] meters
+fn reach(graph: &Graph, from: &EdgeMatch, bound: f64) -> Reachable {
+    let edge = graph.edges().get(&from.edge_id).unwrap();
+    let mut reachable: Reachable = HashMap::new();
+    let mut open_set: PriorityQueue<NodeId, Reverse<NotNan<f64>>> = PriorityQueue::new();
+
+    // leave the edge through its end, or its beginning if allowed
+    let mut exits = vec![(*edge.t(), *edge.distance() - from.offset)];
+    if ! *edge.directed() { exits.push((*edge.s(), from.offset)); }
+    for (node_id, distance) in exits {
+        let distance = distance.max(0.0);
+        if distance < reachable.get(&node_id).map(|r| r.0).unwrap_or(f64::MAX) {
+            reachable.insert(node_id, (distance, None));
+            open_set.push(node_id, Reverse(NotNan::new(distance).unwrap()));
+        }
+    }
+
+    while let Some((node_id, Reverse(distance))) = open_set.pop() {
+        let distance = distance.into_inner();
+        if distance > bound { break; }
+
+        for edge_id in graph.nodes().get(&node_id).unwrap().edges() {
+            let edge = graph.edges().get(edge_id).unwrap();
+            let (neighbour, _) = other_end(edge, &node_id);
+            let tentative = distance + *edge.distance();
+            if tentative < reachable.get(&neighbour).map(|r| r.0).unwrap_or(f64::MAX) {
+                reachable.insert(neighbour, (tentative, Some((node_id, *edge_id))));
+                open_set.push(neighbour, Reverse(NotNan::new(tentative).unwrap()));
+            }
+        }
+    }
+
+    reachable
+}
+
+/// Meters along the graph from [from] to [to] and the node, where the edge
+/// of [to] is entered. None if [to] is not reachable
+fn route_distance(
+    graph: &Graph,
+    reachable: &Reachable,
+    from: &EdgeMatch,
+    to: &EdgeMatch
+) -> Option<(f64, NodeId)> {
+    let edge = graph.edges().get(&to.edge_id).unwrap();
+
+    // both on the same edge, the direction is of no concern for gps noise
+    if from.edge_id == to.edge_id {
+        return Some(((to.offset - from.offset).abs(), *edge.s()));
+    }
+
+    // enter the edge through its beginning, or its end if allowed
+    let mut entries = vec![(*edge.s(), to.offset)];
+    if ! *edge.directed() { entries.push((*edge.t(), *edge.distance() - to.offset)); }
+
+    entries
+        .into_iter()
+        .filter_map(|(node_id, rest)| reachable.get(&node_id).map(|r| (r.0 + rest.max(0.0), node_id)))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+/// Edges leading to [node_id] in riding order
+fn edges_to(reachable: &Reachable, node_id: &NodeId) -> Vec<EdgeId> {
+    let mut edges: Vec<EdgeId> = Vec::new();
+    let mut current = *node_id;
+    while let Some((_, Some((previous, edge_id)))) = reachable.get(&current) {
+        edges.push(*edge_id);
+        current = *previous;
+    }
+    edges.reverse();
+    edges
+}
+
+/// Intersections along the connected [edges], gaps between them are closed
+/// by the shortest path, if possible
+fn to_path(graph: &Graph, edges: &[EdgeId]) -> Path {
+    let edge = |edge_id: &EdgeId| graph.edges().get(edge_id).unwrap();
+
+    let first = match edges.first() {
+        Some(edge_id) => edge(edge_id),
+        None => return Path::default(),
+    };
+    // begin at the end of the first edge, which is not shared with the second
+    let start = match edges.get(1).map(edge) {
+        Some(second) if first.s() == second.s() || first.s() == second.t() => *first.t(),
+        _ => *first.s(),
+    };
+
+    let mut route = Path::new(vec![start], Vec::new());
+    for edge_id in edges {
+        let current = *route.nodes().last().unwrap();
+        let edge = edge(edge_id);
+
+        // the previous edge was only ridden partly, in and out through the same node
+        let length = route.nodes.len();
+        if length > 1 && ! [edge.s(), edge.t()].contains(&&current)
+            && [edge.s(), edge.t()].contains(&&route.nodes[length - 2]) {
+            route.nodes.pop();
+            route.edges.pop();
+        }
+        let current = *route.nodes().last().unwrap();
+
+        let (from, to) = if *edge.s() == current {
+            (current, *edge.t())
+        } else if *edge.t() == current {
+            (current, *edge.s())
+        } else {
+            // close the gap to the beginning of the edge first
+            match a_star(graph, &Weighting::flat(), &Overlap::new(), &current, edge.s()) {
+                Some(mut gap) => route.append(&mut gap),
+                None => continue,
+            }
+            (*edge.s(), *edge.t())
+        };
+        route.append(&mut Path::new(vec![from, to], vec![*edge_id]));
+    }
+
+    route
+}
+
+/// The end of a [ridden] edge closest to [p], if the edge passes within
+/// [corridor] meters of [p] somewhere along its geometry. A long edge may
+/// pass close by, even if both of its intersections are far away
+pub fn passed_by(graph: &Graph, ridden: &HashSet<EdgeId>, p: &Point, corridor: f64) -> Option<NodeId> {
+    let closest = graph.index()
+        .edges_within(p, corridor)
+        .into_iter()
+        .find(|candidate| ridden.contains(&candidate.edge_id))?;
+
+    let edge = graph.edges().get(&closest.edge_id).unwrap();
+    match closest.offset < *edge.distance() / 2.0 {
+        true => Some(*edge.s()),
+        false => Some(*edge.t()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use crate::graph::{Node as GraphNode, Edge as GraphEdge};
+    use crate::parser::{data_from_pbf, weave};
+    use crate::router::closest_intersection;
+
+    #[test]
+    fn match_edges_stays_on_road() {
+        // 3<-->4<-->5   60 m north of the southern road
+        // |         |
+        // 0<-->1<-->2
+        let mut graph_nodes: HashMap<NodeId, GraphNode> = HashMap::new();
+        for i in 0..3 {
+            let x = 7.0 + i as f64 * 0.002;
+            graph_nodes.insert(i, GraphNode::new(i, Point::new(x, 51.0)));
+            graph_nodes.insert(i + 3, GraphNode::new(i + 3, Point::new(x, 51.00054)));
+        }
+
+        let mut graph_edges: HashMap<EdgeId, GraphEdge> = HashMap::new();
+        let ways: [(NodeId, NodeId); 6] = [(0, 1), (1, 2), (3, 4), (4, 5), (0, 3), (2, 5)];
+        for (edge_id, (s, t)) in ways.into_iter().enumerate() {
+            let edge_id = edge_id as EdgeId;
+            let distance = graph_nodes.get(&s).unwrap().point()
+                .haversine_distance(graph_nodes.get(&t).unwrap().point());
+            graph_edges.insert(edge_id, GraphEdge::new(edge_id, distance, false, vec![s, t]));
+            graph_nodes.get_mut(&s).unwrap().insert_edge(edge_id);
+            graph_nodes.get_mut(&t).unwrap().insert_edge(edge_id);
+        }
+        let graph = Graph::new(graph_nodes, graph_edges);
+
+        // the third fix is closer to the northern road
+        let track = vec![
+            Point::new(7.0003, 51.00005),
+            Point::new(7.0015, 50.99995),
+            Point::new(7.0025, 51.00035),
+            Point::new(7.0035, 51.00002),
+            Point::new(7.0048, 50.99998),
+        ];
+        assert_eq!(vec![0, 1], match_edges(&graph, &track));
+
+        let route = match_track(&graph, &track).unwrap();
+        assert_eq!(&vec![0, 1, 2], route.nodes());
+
+        // about 70 m from the intersections, but 33 m from the road between
+        let ridden: HashSet<EdgeId> = route.edges().iter().copied().collect();
+        assert_eq!(Some(0), passed_by(&graph, &ridden, &Point::new(7.0009, 51.0003), 50.0));
+        // close to the northern road only, which is not ridden
+        assert_eq!(None, passed_by(&graph, &ridden, &Point::new(7.001, 51.0005), 50.0));
+
+        // a track somewhere else and an empty one follow no road
+        let elsewhere = vec![Point::new(8.0, 52.0), Point::new(8.001, 52.0)];
+        assert_eq!(Err(String::from("track does not follow any road of the map")), match_track(&graph, &elsewhere).map(|_| ()));
+        assert!(match_track(&graph, &[]).is_err());
+    }
+
+    #[test]
+    fn match_edges_noisy_route() {
+        let data = data_from_pbf("resources/dortmund_sued.osm.pbf");
+        let graph = weave(&data);
+
+        let everywhere: HashSet<NodeId> = graph.nodes().keys().copied().collect();
+        let start = closest_intersection(&graph, &Point::new(7.4058927, 51.4896161), &everywhere).unwrap();
+        let end = closest_intersection(&graph, &Point::new(7.4250, 51.4960), &everywhere).unwrap();
+        let ridden = a_star(&graph, &Weighting::flat(), &Overlap::new(), &start, &end).unwrap();
+        let trip = crate::router::postprocessor::Trip::new(&graph, &ridden, Vec::new());
+
+        // a fix every 20 m with up to 10 m of noise in both directions
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut track: Vec<Point> = Vec::new();
+        let mut next = 0.0;
+        for trip_point in trip.points() {
+            if trip_point.distance < next { continue; }
+            next = trip_point.distance + 20.0;
+            let noise: (f64, f64) = (rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+            track.push(Point::new(
+                trip_point.point.x() + noise.0 * 10.0 / 70_000.0,
+                trip_point.point.y() + noise.1 * 10.0 / 111_000.0,
+            ));
+        }
+
+        let matched: HashSet<EdgeId> = match_edges(&graph, &track).into_iter().collect();
+        let length = |edge_id: &EdgeId| *graph.edges().get(edge_id).unwrap().distance();
+        let total: f64 = ridden.edges().iter().map(length).sum();
+        let found: f64 = ridden.edges().iter().filter(|e| matched.contains(e)).map(length).sum();
+        assert!(total > 1000.0);
+        assert!(found / total > 0.9, "only {:.0} of {:.0} m matched", found, total);
+    }
+}
@@ -0,0 +1,422 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use ordered_float::NotNan;
+use priority_queue::PriorityQueue;
+
+use crate::graph::{EdgeId, Graph, NodeId};
+use crate::router::{other_end, Direction, Path, Plan, Stops, Weighting};
+
+/// Only the first candidates are considered, every one of them needs a
+/// search through the graph
+const MAX_CANDIDATES: usize = 30;
+/// Perturbations of the best tour, before it is taken
+const ITERATIONS: usize = 200;
+
+/// Least cost paths between all pairs of places and their ridden meters,
+/// place 0 is the start. The paths back to it lead to the end instead, so
+/// a trip to somewhere else is solved like a loop
+struct Distances {
+    paths: Vec<Vec<Option<Path>>>,
+    meters: Vec<Vec<f64>>,
+}
+
+/// Trip from the start through the via points and the [candidates] to the
+/// end of the [stops] collecting as much scenery as possible, without getting
+/// longer than the [window] (min, max) in meters allows (orienteering
+/// problem). Every candidate is worth one point plus the greatness of its
+/// node, the via points are visited in any case.
+///
+/// Solved on the shortest paths between all candidates with iterated local
+/// search: the tour is shortened by 2-opt and or-opt moves, the saved length
+/// is filled with further candidates and some visits are dropped again to
+/// escape local optima. The best tour is ridden along its shortest paths,
+/// so it may repeat roads. Loops are ridden in [direction], if that fits
+/// into the window. Fails with the via points, that can not be ridden to from the start or
+/// on to the end or from each other, e.g. because of too steep roads
+pub fn orienteering(
+    graph: &Graph,
+    weighting: &Weighting,
+    stops: &Stops,
+    candidates: &[NodeId],
+    window: (f64, f64),
+    direction: &Direction
+) -> Result<Plan, String> {
+    let (start, end) = (&stops.start, &stops.end);
+    let mut places: Vec<NodeId> = vec![*start];
+    for via in &stops.via {
+        if ! places.contains(via) && via != end { places.push(*via); }
+    }
+    let mandatory = places.len() - 1;
+    for candidate in candidates {
+        if places.len() > MAX_CANDIDATES + mandatory { break; }
+        if ! places.contains(candidate) && candidate != end { places.push(*candidate); }
+    }
+    let scores: Vec<f64> = places
+        .iter()
+        .map(|node_id| 1.0 + *graph.nodes().get(node_id).unwrap().greatness() as f64 / 255.0)
+        .collect();
+    let distances = Distances::new(graph, weighting, &places, end);
+
+    let position = |i: usize| {
+        let p = graph.nodes().get(&places[i]).unwrap().point();
+        format!("({:.5}, {:.5})", p.y(), p.x())
+    };
+    // they would be inserted at any cost
+    let unreachable: Vec<String> = (1..=mandatory)
+        .filter(|i| distances.meters[0][*i].is_infinite() || distances.meters[*i][0].is_infinite())
+        .map(position)
+        .collect();
+    if ! unreachable.is_empty() {
+        return Err(format!("the via points {} can not be reached", unreachable.join(", ")));
+    }
+
+    let mut tour = solve(&distances.meters, &scores, window.1, mandatory);
+    // one-way streets may make the other way round too long
+    let waypoints: Vec<NodeId> = tour.iter().map(|i| places[*i]).collect();
+    let reversed: Vec<usize> = tour.iter().rev().copied().collect();
+    if start == end && Direction::of(graph, start, &waypoints).is_some_and(|d| d != *direction)
+        && length(&distances.meters, &reversed) <= window.1 {
+        tour = reversed;
+    }
+    let fits = (window.0..=window.1).contains(&length(&distances.meters, &tour));
+
+    // ride from place to place and on to the end
+    tour.insert(0, 0);
+    tour.push(0);
+    let mut route = Path::new(vec![*start], Vec::new());
+    for pair in tour.windows(2) {
+        let mut part = match distances.paths[pair[0]][pair[1]].clone() {
+            Some(part) => part,
+            None => return Err(format!(
+                "the via points {} and {} can not be ridden one after the other", position(pair[0]), position(pair[1])
+            )),
+        };
+        route.append(&mut part);
+    }
+    Ok(Plan { route, fits, skipped: Vec::new() })
+}
+
+impl Distances {
+    fn new(graph: &Graph, weighting: &Weighting, places: &[NodeId], end: &NodeId) -> Self {
+        // the start is left for the end
+        let mut targets = places.to_vec();
+        targets[0] = *end;
+
+        let mut paths = Vec::new();
+        let mut meters = Vec::new();
+        for from in places {
+            let found = shortest_paths(graph, weighting, from, &targets);
+            let row: Vec<Option<Path>> = targets.iter().map(|to| found.get(to).cloned()).collect();
+            meters.push(row
+                .iter()
+                .map(|path| match path {
+                    Some(p) => p.edges().iter().map(|e| graph.edges().get(e).unwrap().distance()).sum(),
+                    None => f64::INFINITY,
+                })
+                .collect()
+            );
+            paths.push(row);
+        }
+        Self { paths, meters }
+    }
+}
+
+/// Least cost paths from [from] to all [targets], the search ends as soon
+/// as every target is reached
+fn shortest_paths(graph: &Graph, weighting: &Weighting, from: &NodeId, targets: &[NodeId]) -> HashMap<NodeId, Path> {
+    let mut came_from: HashMap<NodeId, (NodeId, EdgeId)> = HashMap::new();
+    let mut costs: HashMap<NodeId, f64> = HashMap::new();
+    let mut settled: HashSet<NodeId> = HashSet::new();
+    let mut open_set: PriorityQueue<NodeId, Reverse<NotNan<f64>>> = PriorityQueue::new();
+    costs.insert(*from, 0.0);
+    open_set.push(*from, Reverse(NotNan::new(0.0).unwrap()));
+
+    let mut missing: HashSet<NodeId> = targets.iter().copied().collect();
+    while let Some((node_id, _)) = open_set.pop() {
+        settled.insert(node_id);
+        missing.remove(&node_id);
+        if missing.is_empty() { break; }
+
+        for edge_id in graph.nodes().get(&node_id).unwrap().edges() {
+            let edge = graph.edges().get(edge_id).unwrap();
+            let (neighbour, forward) = other_end(edge, &node_id);
+            // the node is in the middle of the edge or the edge is a one-way
+            if neighbour == node_id || settled.contains(&neighbour) { continue; }
+            let cost = match weighting.cost(edge, forward) {
+                Some(c) => costs[&node_id] + c,
+                None => continue,
+            };
+            if cost < *costs.get(&neighbour).unwrap_or(&f64::MAX) {
+                costs.insert(neighbour, cost);
+                came_from.insert(neighbour, (node_id, *edge_id));
+                open_set.push(neighbour, Reverse(NotNan::new(cost).unwrap()));
+            }
+        }
+    }
+
+    targets
+        .iter()
+        .filter(|target| settled.contains(target))
+        .map(|target| {
+            let mut nodes = vec![*target];
+            let mut edges = Vec::new();
+            while let Some((previous, edge_id)) = came_from.get(nodes.last().unwrap()) {
+                nodes.push(*previous);
+                edges.push(*edge_id);
+            }
+            nodes.reverse();
+            edges.reverse();
+            (*target, Path::new(nodes, edges))
+        })
+        .collect()
+}
+
+/// Places to visit in their order, the start 0 is left out. Every place
+/// has its [scores] and [meters] between them, the tour is at most
+/// [budget] meters long. The places 1 to [mandatory] are always visited
+fn solve(meters: &[Vec<f64>], scores: &[f64], budget: f64, mandatory: usize) -> Vec<usize> {
+    let mut tour: Vec<usize> = Vec::new();
+    // even if they do not fit into the budget
+    for place in 1..=mandatory {
+        insert_cheapest(meters, &mut tour, place);
+    }
+    improve(meters, scores, budget, &mut tour);
+    let mut best = tour.clone();
+
+    for iteration in 0..ITERATIONS {
+        if tour.is_empty() { break; }
+        // drop a few consecutive visits at a changing position
+        let count = (1 + iteration % 3).min(tour.len());
+        let position = (iteration * 7) % (tour.len() - count + 1);
+        let dropped: Vec<usize> = tour.drain(position..position + count).collect();
+        for place in dropped.into_iter().filter(|place| *place <= mandatory) {
+            insert_cheapest(meters, &mut tour, place);
+        }
+        improve(meters, scores, budget, &mut tour);
+
+        let better = score(scores, &tour).total_cmp(&score(scores, &best))
+            .then(length(meters, &best).total_cmp(&length(meters, &tour)));
+        if better.is_gt() {
+            best = tour.clone();
+        } else {
+            tour = best.clone();
+        }
+    }
+
+    best
+}
+
+/// Shorten the [tour] and fill it up again, until no candidate fits anymore
+fn improve(meters: &[Vec<f64>], scores: &[f64], budget: f64, tour: &mut Vec<usize>) {
+    loop {
+        two_opt(meters, tour);
+        or_opt(meters, tour);
+        if ! insert_best(meters, scores, budget, tour) { break; }
+    }
+}
+
+/// Sum of the [scores] of all places of the [tour]
+fn score(scores: &[f64], tour: &[usize]) -> f64 {
+    tour.iter().map(|i| scores[*i]).sum()
+}
+
+/// Meters from the start through the [tour] and back
+fn length(meters: &[Vec<f64>], tour: &[usize]) -> f64 {
+    let mut previous = 0;
+    let mut length = 0.0;
+    for place in tour.iter().chain([0].iter()) {
+        length += meters[previous][*place];
+        previous = *place;
+    }
+    length
+}
+
+/// Add the unvisited place with the most score per additional meter at its
+/// cheapest position, returns false if none fits into the [budget]
+fn insert_best(meters: &[Vec<f64>], scores: &[f64], budget: f64, tour: &mut Vec<usize>) -> bool {
+    let current = length(meters, tour);
+    let mut best: Option<(f64, usize, usize)> = None;
+    for (place, score) in scores.iter().enumerate().skip(1) {
+        if tour.contains(&place) { continue; }
+        for position in 0..=tour.len() {
+            let added = added(meters, tour, place, position);
+            // unreachable places have no finite distance
+            if added.is_nan() || current + added > budget { continue; }
+
+            let ratio = score / added.max(1.0);
+            if best.is_none_or(|(b, _, _)| ratio > b) {
+                best = Some((ratio, place, position));
+            }
+        }
+    }
+
+    match best {
+        Some((_, place, position)) => { tour.insert(position, place); true },
+        None => false,
+    }
+}
+
+/// Insert [place] into the [tour], where it adds the fewest meters
+fn insert_cheapest(meters: &[Vec<f64>], tour: &mut Vec<usize>, place: usize) {
+    let position = (0..=tour.len())
+        .min_by(|a, b| added(meters, tour, place, *a).total_cmp(&added(meters, tour, place, *b)))
+        .unwrap();
+    tour.insert(position, place);
+}
+
+/// Meters added to the [tour] by visiting [place] at [position]
+fn added(meters: &[Vec<f64>], tour: &[usize], place: usize, position: usize) -> f64 {
+    let before = if position == 0 { 0 } else { tour[position - 1] };
+    let after = tour.get(position).copied().unwrap_or(0);
+    meters[before][place] + meters[place][after] - meters[before][after]
+}
+
+/// Reverse parts of the [tour], as long as it gets shorter. The distances
+/// differ by direction, so the whole tour is measured
+fn two_opt(meters: &[Vec<f64>], tour: &mut [usize]) {
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 0..tour.len() {
+            for j in i + 1..tour.len() {
+                let before = length(meters, tour);
+                tour[i..=j].reverse();
+                if length(meters, tour) < before - 1e-9 {
+                    improved = true;
+                } else {
+                    tour[i..=j].reverse();
+                }
+            }
+        }
+    }
+}
+
+/// Move chains of up to three places to another position of the [tour], as
+/// long as it gets shorter
+fn or_opt(meters: &[Vec<f64>], tour: &mut Vec<usize>) {
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for chain in 1..=3.min(tour.len()) {
+            for i in 0..=tour.len() - chain {
+                let before = length(meters, tour);
+                let moved: Vec<usize> = tour.drain(i..i + chain).collect();
+                let position = (0..=tour.len())
+                    .min_by(|a, b| {
+                        let with = |p: usize| {
+                            let mut candidate = tour.clone();
+                            candidate.splice(p..p, moved.iter().copied());
+                            length(meters, &candidate)
+                        };
+                        with(*a).total_cmp(&with(*b))
+                    })
+                    .unwrap();
+                let mut candidate = tour.clone();
+                candidate.splice(position..position, moved.iter().copied());
+                if length(meters, &candidate) < before - 1e-9 {
+                    *tour = candidate;
+                    improved = true;
+                } else {
+                    tour.splice(i..i, moved);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Symmetric distances between points on a line
+    fn on_a_line(positions: &[f64]) -> Vec<Vec<f64>> {
+        positions
+            .iter()
+            .map(|a| positions.iter().map(|b| (a - b).abs()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn solve_within_budget() {
+        // start at 0, places at 1, 2, 3 and 10 km
+        let meters = on_a_line(&[0.0, 1_000.0, 2_000.0, 3_000.0, 10_000.0]);
+        let scores = vec![0.0, 1.0, 1.0, 1.0, 2.0];
+
+        // out to 3 km and back visits all three close places
+        let tour = solve(&meters, &scores, 6_500.0, 0);
+        assert_eq!(3, tour.len());
+        assert!((length(&meters, &tour) - 6_000.0).abs() < 1e-9);
+
+        // with enough budget every place is visited
+        let tour = solve(&meters, &scores, 20_000.0, 0);
+        assert_eq!(4, tour.len());
+        let tour = solve(&meters, &scores, 500.0, 0);
+        assert!(tour.is_empty());
+
+        // the place at 10 km is visited in any case
+        let meters = on_a_line(&[0.0, 10_000.0, 1_000.0, 2_000.0, 3_000.0]);
+        let tour = solve(&meters, &scores, 6_500.0, 1);
+        assert_eq!(vec![1], tour);
+    }
+
+    #[test]
+    fn two_opt_untangles() {
+        // corners of a square with a crossing tour 0 -> 2 -> 1 -> 3 -> 0
+        let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)];
+        let meters: Vec<Vec<f64>> = corners
+            .iter()
+            .map(|a: &(f64, f64)| corners.iter().map(|b| ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()).collect())
+            .collect();
+        let mut tour = vec![2, 1, 3];
+        two_opt(&meters, &mut tour);
+        assert!((length(&meters, &tour) - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn via_without_way_back_fails() {
+        use geo::Point;
+        use crate::graph::{Edge, Node};
+
+        // 0<-->1--->2, the via point 2 is a dead end of a one-way street
+        let mut nodes: HashMap<NodeId, Node> = HashMap::new();
+        for node_id in 0..3 {
+            nodes.insert(node_id, Node::new(node_id, Point::new(7.0 + node_id as f64 * 0.001, 51.0)));
+        }
+        let mut edges: HashMap<EdgeId, Edge> = HashMap::new();
+        for (edge_id, s, t, directed) in [(0, 0, 1, false), (1, 1, 2, true)] {
+            edges.insert(edge_id, Edge::new(edge_id, 70.0, directed, vec![s, t]));
+            nodes.get_mut(&s).unwrap().insert_edge(edge_id);
+            nodes.get_mut(&t).unwrap().insert_edge(edge_id);
+        }
+        let graph = Graph::new(nodes, edges);
+
+        let stops = Stops { start: 0, via: vec![2], end: 0 };
+        let result = orienteering(&graph, &Weighting::flat(), &stops, &[1], (0.0, 1_000.0), &Direction::Clockwise);
+        assert_eq!(Err(String::from("the via points (51.00000, 7.00200) can not be reached")), result.map(|_| ()));
+
+        let stops = Stops { start: 0, via: vec![1], end: 0 };
+        let plan = orienteering(&graph, &Weighting::flat(), &stops, &[2], (0.0, 1_000.0), &Direction::Clockwise).unwrap();
+        assert_eq!(&vec![0, 1, 0], plan.route.nodes());
+        assert!(plan.fits);
+        let plan = orienteering(&graph, &Weighting::flat(), &stops, &[2], (500.0, 1_000.0), &Direction::Clockwise).unwrap();
+        assert!(! plan.fits);
+
+        // 0--->1--->3 and 0--->2--->3, both via points lead to the end only
+        let mut nodes: HashMap<NodeId, Node> = HashMap::new();
+        for node_id in 0..4 {
+            nodes.insert(node_id, Node::new(node_id, Point::new(7.0 + node_id as f64 * 0.001, 51.0)));
+        }
+        let mut edges: HashMap<EdgeId, Edge> = HashMap::new();
+        for (edge_id, s, t) in [(0, 0, 1), (1, 1, 3), (2, 0, 2), (3, 2, 3)] {
+            edges.insert(edge_id, Edge::new(edge_id, 70.0, true, vec![s, t]));
+            nodes.get_mut(&s).unwrap().insert_edge(edge_id);
+            nodes.get_mut(&t).unwrap().insert_edge(edge_id);
+        }
+        let graph = Graph::new(nodes, edges);
+
+        let stops = Stops { start: 0, via: vec![1, 2], end: 3 };
+        let result = orienteering(&graph, &Weighting::flat(), &stops, &[], (0.0, 1_000.0), &Direction::Clockwise);
+        assert!(result.is_err_and(|e| e.ends_with("can not be ridden one after the other")));
+    }
+}
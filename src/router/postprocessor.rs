@@ -1,119 +1,385 @@
-use std::collections::HashSet;
 use std::str::FromStr;
 
+use geo::BoundingRect;
+use geo::HaversineBearing;
+use geo::LineString;
 use geo::Point;
 use gpx::Gpx;
+use gpx::Link;
+use gpx::Metadata;
+use gpx::Person;
 use gpx::Route;
+use gpx::Track;
+use gpx::TrackSegment;
 use gpx::Waypoint;
+use time::OffsetDateTime;
 
 use crate::graph::EdgeId;
 use crate::graph::Graph;
 use crate::graph::NodeId;
 use crate::graph::Edge as GraphEdge;
+use crate::parser::Poi;
+use crate::router::Path;
 
-pub fn intersections_to_gpx(graph: &Graph, main_nodes: &Vec<NodeId>) -> Gpx {
-    let mut waypoints: Vec<Waypoint> = Vec::new();
+mod cues;
+mod fit;
+mod geojson;
+mod kml;
+mod simplify;
+mod tcx;
 
-    let first = graph.nodes().get(&main_nodes[0]).unwrap();
-    waypoints.push(Waypoint::new(*first.point()));
-    for i in 1..main_nodes.len() {
-        let points = intermediary_points(graph, &main_nodes[i - 1], &main_nodes[i]);
-        waypoints.append(&mut points
+pub use cues::{cues_to_route, cues_to_string, trip_to_cues, CueFormat};
+pub use fit::trip_to_fit;
+pub use geojson::trip_to_geojson;
+pub use kml::trip_to_kml;
+pub use simplify::Simplification;
+pub use tcx::trip_to_tcx;
+
+/// File format of the result
+#[derive(serde::Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Gpx,
+    GeoJson,
+    Kml,
+    Tcx,
+    Fit,
+}
+
+/// Which kinds of gpx elements describe the route
+#[derive(serde::Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GpxContent {
+    /// every point as <rte>
+    #[default]
+    Route,
+    /// every point as <trk> with one <trkseg>
+    Track,
+    /// only the decision points as <rte>, devices calculate the way between
+    DecisionPoints,
+    /// every point as <trk> and only the decision points as <rte>
+    Both,
+}
+
+/// Turns less sharp than this (in degrees) are just following the road
+const MIN_TURN: f64 = 30.0;
+
+/// A route resolved into every single point, shared by all output formats
+pub struct Trip {
+    /// Every point in riding order
+    points: Vec<TripPoint>,
+    /// Intersections in riding order
+    main_nodes: Vec<NodeId>,
+    /// Position of every main node inside of points
+    at: Vec<usize>,
+    /// Ridden edges in order
+    segments: Vec<EdgeId>,
+    /// Places the route passes by
+    pois: Vec<Poi>,
+}
+
+/// One point of a [Trip]
+pub struct TripPoint {
+    pub point: Point,
+    /// Height above sea level in meters, if known
+    pub elevation: Option<f64>,
+    /// Meters ridden since the start of the trip
+    pub distance: f64,
+}
+
+impl Trip {
+    /// Resolve the [route] along the edges it follows into every point
+    pub fn new(graph: &Graph, route: &Path, pois: Vec<Poi>) -> Self {
+        let main_nodes = route.nodes();
+        let mut nodes: Vec<NodeId> = Vec::new();
+        let mut at: Vec<usize> = Vec::new();
+
+        nodes.push(main_nodes[0]);
+        at.push(0);
+        for (i, edge_id) in route.edges().iter().enumerate() {
+            nodes.append(&mut intermediary_points(graph, edge_id, &main_nodes[i], &main_nodes[i + 1]));
+
+            at.push(nodes.len());
+            nodes.push(main_nodes[i + 1]);
+        }
+
+        let mut points: Vec<TripPoint> = Vec::new();
+        for node_id in nodes {
+            let node = graph.nodes().get(&node_id).unwrap();
+            let distance = match points.last() {
+                Some(previous) => previous.distance +
+                    geo::HaversineDistance::haversine_distance(&previous.point, node.point()),
+                None => 0.0,
+            };
+            points.push(TripPoint { point: *node.point(), elevation: *node.elevation(), distance });
+        }
+
+        Self { points, main_nodes: main_nodes.clone(), at, segments: route.edges().clone(), pois }
+    }
+
+    pub fn points(&self) -> &Vec<TripPoint> {
+        &self.points
+    }
+
+    pub fn segments(&self) -> &Vec<EdgeId> {
+        &self.segments
+    }
+
+    pub fn pois(&self) -> &Vec<Poi> {
+        &self.pois
+    }
+
+    pub fn main_nodes(&self) -> &Vec<NodeId> {
+        &self.main_nodes
+    }
+
+    /// Position of every main node inside of the points
+    pub fn main_node_positions(&self) -> &Vec<usize> {
+        &self.at
+    }
+
+    /// Total length in meters
+    pub fn distance(&self) -> f64 {
+        self.points.last().map(|p| p.distance).unwrap_or(0.0)
+    }
+
+    pub fn linestring(&self) -> LineString {
+        self.points.iter().map(|p| p.point).collect()
+    }
+
+    /// Summed up height gain and loss in meters
+    pub fn elevation_gain(&self) -> (f64, f64) {
+        let heights: Vec<Option<f64>> = self.points.iter().map(|p| p.elevation).collect();
+        crate::elevation::climb(&heights)
+    }
+
+    /// Positions of the main nodes, where the route changes its direction.
+    /// The first and the last point are always included
+    pub fn decision_points(&self) -> Vec<usize> {
+        let mut decisions: Vec<usize> = Vec::new();
+        for (n, i) in self.at.iter().enumerate() {
+            if n == 0 || n == self.at.len() - 1 {
+                decisions.push(*i);
+                continue;
+            }
+
+            if let Some(angle) = self.turn_angle(*i) {
+                if angle.abs() >= MIN_TURN { decisions.push(*i); }
+            }
+        }
+        // after a simplification several main nodes may share a point
+        decisions.dedup();
+        decisions
+    }
+
+    /// Change of direction in degrees at the point [i], positive turns are to
+    /// the right. None if the direction can not be determined
+    pub fn turn_angle(&self, i: usize) -> Option<f64> {
+        let point = self.points.get(i)?.point;
+        // skip duplicates, they have no bearing
+        let before = self.points[..i].iter().rev().map(|p| p.point).find(|p| *p != point)?;
+        let after = self.points.get(i + 1..)?.iter().map(|p| p.point).find(|p| *p != point)?;
+
+        let incoming = before.haversine_bearing(point);
+        let outgoing = point.haversine_bearing(after);
+
+        // normalize into -180 .. 180
+        let mut angle = outgoing - incoming;
+        if angle > 180.0 { angle -= 360.0; }
+        if angle < -180.0 { angle += 360.0; }
+        Some(angle)
+    }
+
+    /// Turns and visited pois in riding order
+    pub fn course_points(&self) -> Vec<CoursePoint> {
+        let mut course_points: Vec<CoursePoint> = Vec::new();
+        for i in self.decision_points() {
+            let angle = match self.turn_angle(i) {
+                Some(a) => a,
+                None => continue, // start and end
+            };
+            let kind = if angle > 0.0 { CoursePointKind::Right } else { CoursePointKind::Left };
+            course_points.push(CoursePoint {
+                at: i,
+                name: String::from(kind.name()),
+                kind,
+                notes: format!("turn {:.0} degrees", angle.abs()),
+            });
+        }
+        for poi in self.pois.iter() {
+            course_points.push(CoursePoint {
+                at: self.nearest(&poi.point),
+                name: poi.name.clone().unwrap_or(poi.category.clone()),
+                kind: CoursePointKind::Generic,
+                notes: poi.category.clone(),
+            });
+        }
+        course_points.sort_by_key(|c| c.at);
+        course_points
+    }
+
+    /// Position of the trip point closest to [p]
+    pub fn nearest(&self, p: &Point) -> usize {
+        let mut nearest = (0, f64::MAX);
+        for (i, trip_point) in self.points.iter().enumerate() {
+            let distance = geo::HaversineDistance::haversine_distance(p, &trip_point.point);
+            if distance < nearest.1 { nearest = (i, distance); }
+        }
+        nearest.0
+    }
+}
+
+/// Noteworthy point along a [Trip], shown by bike computers
+pub struct CoursePoint {
+    /// Position inside of the trip points
+    pub at: usize,
+    pub name: String,
+    pub kind: CoursePointKind,
+    /// Additional explanation
+    pub notes: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CoursePointKind {
+    Left,
+    Right,
+    Generic,
+}
+
+impl CoursePointKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Generic => "Generic",
+        }
+    }
+}
+
+pub fn trip_to_gpx(trip: &Trip, content: &GpxContent) -> Gpx {
+    let waypoints: Vec<Waypoint> = trip.points.iter().map(to_waypoint).collect();
+    let decisions = || -> Vec<Waypoint> {
+        trip.decision_points()
             .iter()
-            .map(|p| Waypoint::new(*p))
+            .map(|i| waypoints[*i].clone())
             .collect()
-        );
+    };
 
-        let i_th = graph.nodes().get(&main_nodes[i]).unwrap();
-        waypoints.push(Waypoint::new(*i_th.point()));
+    let mut routes: Vec<Route> = Vec::new();
+    let mut tracks: Vec<Track> = Vec::new();
+    match content {
+        GpxContent::Route => routes.push(to_route(waypoints.clone())),
+        GpxContent::Track => tracks.push(to_track(waypoints.clone())),
+        GpxContent::DecisionPoints => routes.push(to_route(decisions())),
+        GpxContent::Both => {
+            routes.push(to_route(decisions()));
+            tracks.push(to_track(waypoints.clone()));
+        },
     }
 
-    let route = Route {
-        name: Some(String::from_str("roundtrip").unwrap()),
-        comment: None,
-        description: None,
-        source: None,
-        links: Vec::new(),
-        number: None,
-        _type: None,
-        points: waypoints
-    };
-    
     Gpx {
         version: gpx::GpxVersion::Gpx11,
         creator: Some(String::from_str("nice-bike-roundtrips-rs").unwrap()),
-        metadata: None,
-        waypoints: Vec::new(),
-        tracks: Vec::new(),
-        routes: vec![route],
+        metadata: Some(metadata(trip)),
+        waypoints: trip.pois.iter().map(poi_to_waypoint).collect(),
+        tracks,
+        routes,
     }
 }
 
+/// a visited place with its name, kind and a link to OpenStreetMap
+fn poi_to_waypoint(poi: &Poi) -> Waypoint {
+    let mut waypoint = Waypoint::new(poi.point);
+    waypoint.name = Some(poi.name.clone().unwrap_or(poi.category.clone()));
+    waypoint.description = Some(poi.category.clone());
+    waypoint.symbol = Some(String::from_str(symbol(&poi.category)).unwrap());
+    waypoint.links.push(Link {
+        href: poi.url(),
+        text: Some(String::from_str("OpenStreetMap").unwrap()),
+        _type: None,
+    });
+    waypoint
+}
 
-// all points that are between from and to, sorted in correct diretion
-fn intermediary_points(graph: &Graph, from: &NodeId, to: &NodeId) -> Vec<Point> {
-    let from_edges: HashSet<EdgeId> = edges_to_hs(graph, from);
-    let to_edges: HashSet<EdgeId> = edges_to_hs(graph, to);
+/// gpx symbol name (as known by Garmin devices) for a poi category
+fn symbol(category: &str) -> &'static str {
+    match category.split('=').next().unwrap_or("") {
+        "tourism" => "Scenic Area",
+        "historic" => "Museum",
+        "man_made" => "Building",
+        "natural" | "landuse" => "Park",
+        _ => "Waypoint",
+    }
+}
 
-    let shared_edge = from_edges
-        .intersection(&to_edges)
-        .into_iter()
-        .fold(None, |result: Option<EdgeId>, edge_id| {
-            match result {
-                None => Some(*edge_id),
-                Some(_) => result,
-                // Some(_) => panic!(), // there exist more than one edge ...
-            }
-        })
-        .unwrap();
+fn to_route(points: Vec<Waypoint>) -> Route {
+    Route {
+        name: Some(String::from_str("roundtrip").unwrap()),
+        points,
+        ..Default::default()
+    }
+}
 
-    let shared_edge = graph.edges().get(&shared_edge).unwrap();
+fn to_track(points: Vec<Waypoint>) -> Track {
+    let mut segment = TrackSegment::new();
+    segment.points = points;
 
-    let from_index = position_in_edge(&shared_edge, &from);
-    let to_index = position_in_edge(&shared_edge, to);
+    Track {
+        name: Some(String::from_str("roundtrip").unwrap()),
+        segments: vec![segment],
+        ..Default::default()
+    }
+}
 
-    let intermediary: Vec<Point>;
-    if from_index > to_index {
-        intermediary = shared_edge
-            .nodes()
-            .iter()
-            .enumerate()
-            .filter_map(|(i, node_id)| {
-                // we the way in reverse, so the index names are a bit unintuitive
-                if i > to_index && i < from_index {
-                    return Some(graph.nodes().get(node_id).unwrap().point().clone());
-                }
-                None
-            })
-            .rev()
-            .collect()
-    } else {
-        intermediary = shared_edge
-            .nodes()
-            .iter()
-            .enumerate()
-            .filter_map(|(i, node_id)| {
-                if i > from_index && i < to_index {
-                    return Some(graph.nodes().get(node_id).unwrap().point().clone());
-                }
-                None
-            })
-            .collect();
+/// name, creation time, bounds and author of the gpx file
+fn metadata(trip: &Trip) -> Metadata {
+    Metadata {
+        name: Some(String::from_str("roundtrip").unwrap()),
+        author: Some(Person {
+            name: Some(String::from_str("nice-bike-roundtrips-rs").unwrap()),
+            email: None,
+            link: Some(Link {
+                href: String::from_str("https://github.com/chereskata/nice-bike-roundtrips-rs").unwrap(),
+                text: None,
+                _type: None,
+            }),
+        }),
+        time: Some(OffsetDateTime::now_utc().into()),
+        bounds: trip.linestring().bounding_rect(),
+        ..Default::default()
     }
+}
 
-    intermediary
+/// a trip point as gpx waypoint, including its height if known
+fn to_waypoint(trip_point: &TripPoint) -> Waypoint {
+    let mut waypoint = Waypoint::new(trip_point.point);
+    waypoint.elevation = trip_point.elevation;
+    waypoint
 }
 
-/// the edges of a node as HashSet
-fn edges_to_hs(graph: &Graph, node: &NodeId) -> HashSet<EdgeId> {
-    graph
-        .nodes()
-        .get(node)
-        .unwrap()
-        .edges()
-        .iter()
-        .copied()
-        .collect()
+/// Replace the characters, that have a meaning in xml
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// all nodes of the edge, that are between from and to, sorted in riding direction
+pub fn intermediary_points(graph: &Graph, edge_id: &EdgeId, from: &NodeId, to: &NodeId) -> Vec<NodeId> {
+    let edge = graph.edges().get(edge_id).unwrap();
+
+    let from_index = position_in_edge(edge, from);
+    let to_index = position_in_edge(edge, to);
+
+    if from_index > to_index {
+        // we ride the way in reverse
+        edge.nodes()[to_index + 1..from_index].iter().rev().copied().collect()
+    } else {
+        edge.nodes()[from_index + 1..to_index].to_vec()
+    }
 }
 
 // index of the node in the edge
@@ -131,5 +397,40 @@ fn position_in_edge(edge: &GraphEdge, target: &NodeId) -> usize {
                 },
             }
         })
-        .unwrap()
-}
\ No newline at end of file
+        .expect("route node is not part of the edge it rides on")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decision_points_only_at_turns() {
+        // 0--->1--->2
+        //           |
+        //           v
+        //           3
+        let points: Vec<TripPoint> = vec![
+            Point::new(7.000, 51.001),
+            Point::new(7.001, 51.001),
+            Point::new(7.002, 51.001),
+            Point::new(7.002, 51.000),
+        ]
+        .into_iter()
+        .map(|point| TripPoint { point, elevation: None, distance: 0.0 })
+        .collect();
+        let trip = Trip { points, main_nodes: vec![0, 1, 2, 3], at: vec![0, 1, 2, 3], segments: Vec::new(), pois: Vec::new() };
+
+        let angle = trip.turn_angle(2).unwrap();
+        assert!((angle - 90.0).abs() < 1.0);
+        assert!(trip.turn_angle(1).unwrap().abs() < 1.0);
+
+        assert_eq!(vec![0, 2, 3], trip.decision_points());
+
+        // the route consists of the turns only
+        let gpx = trip_to_gpx(&trip, &GpxContent::DecisionPoints);
+        assert!(gpx.tracks.is_empty());
+        let route: Vec<Point> = gpx.routes[0].points.iter().map(|w| w.point()).collect();
+        assert_eq!(vec![trip.points[0].point, trip.points[2].point, trip.points[3].point], route);
+    }
+}
@@ -0,0 +1,262 @@
+use std::str::FromStr;
+
+use geo::Point;
+use gpx::{Route, Waypoint};
+
+use crate::graph::{Edge as GraphEdge, Graph};
+use crate::router::postprocessor::{Trip, TripPoint};
+
+/// Changes of direction below this angle (in degrees) keep going straight
+const STRAIGHT: f64 = 20.0;
+/// Changes of direction below this angle (in degrees) are slight turns
+const SLIGHT: f64 = 60.0;
+/// Changes of direction above this angle (in degrees) are sharp turns
+const SHARP: f64 = 120.0;
+
+/// File format of the cue sheet
+#[derive(serde::Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CueFormat {
+    #[default]
+    Text,
+    Csv,
+    Markdown,
+}
+
+/// One instruction of a turn by turn cue sheet
+pub struct Cue {
+    pub point: Point,
+    /// Height above sea level in meters, if known
+    pub elevation: Option<f64>,
+    /// Meters ridden since the start
+    pub distance: f64,
+    pub instruction: String,
+}
+
+/// Derive the maneuvers along the [trip] from the edges it rides on
+pub fn trip_to_cues(graph: &Graph, trip: &Trip) -> Vec<Cue> {
+    let mut cues: Vec<Cue> = Vec::new();
+    let segments = trip.segments();
+    if segments.is_empty() { return cues; }
+
+    let edge = |i: usize| graph.edges().get(&segments[i]).unwrap();
+
+    let first = &trip.points()[0];
+    cues.push(Cue {
+        point: first.point,
+        elevation: first.elevation,
+        distance: 0.0,
+        instruction: format!("start on {}", street(edge(0))),
+    });
+
+    // intersections passed inside of the current roundabout
+    let mut exits = 0;
+    for i in 1..segments.len() {
+        let (before, after) = (edge(i - 1), edge(i));
+        let at = trip.main_node_positions()[i];
+        let TripPoint { point, elevation, distance } = trip.points()[at];
+
+        match (is_roundabout(before), is_roundabout(after)) {
+            (false, true) => {
+                exits = 0;
+                cues.push(Cue { point, elevation, distance, instruction: String::from("enter the roundabout") });
+                continue;
+            },
+            (true, true) => {
+                if graph.nodes().get(&trip.main_nodes()[i]).unwrap().edges().len() > 2 { exits += 1; }
+                continue;
+            },
+            (true, false) => {
+                cues.push(Cue {
+                    point,
+                    elevation,
+                    distance,
+                    instruction: format!("take exit {} onto {}", exits + 1, street(after)),
+                });
+                continue;
+            },
+            (false, false) => (),
+        }
+
+        let angle = match trip.turn_angle(at) {
+            Some(a) => a,
+            None => continue,
+        };
+        let same_street = street(before) == street(after);
+
+        let instruction = match maneuver(angle) {
+            Some(turn) => format!("{} onto {}", turn, street(after)),
+            // going straight is only worth a note, when the street changes
+            None if ! same_street && has_name(after) => format!("continue onto {}", street(after)),
+            None => continue,
+        };
+        cues.push(Cue { point, elevation, distance, instruction });
+    }
+
+    let last = trip.points().last().unwrap();
+    let arrival = match trip.points().first().unwrap().point == last.point {
+        true => "arrive at the start",
+        false => "arrive at the destination",
+    };
+    cues.push(Cue {
+        point: last.point,
+        elevation: last.elevation,
+        distance: trip.distance(),
+        instruction: String::from(arrival),
+    });
+
+    cues
+}
+
+/// Cue sheet as printable document
+pub fn cues_to_string(cues: &[Cue], format: &CueFormat) -> String {
+    let mut sheet = String::new();
+    match format {
+        CueFormat::Text => {
+            for cue in cues {
+                sheet.push_str(&format!("{:>7.2} km  {}\n", cue.distance / 1000.0, cue.instruction));
+            }
+        },
+        CueFormat::Csv => {
+            sheet.push_str("distance_km,instruction\n");
+            for cue in cues {
+                sheet.push_str(&format!(
+                    "{:.2},\"{}\"\n",
+                    cue.distance / 1000.0,
+                    cue.instruction.replace('"', "\"\"")
+                ));
+            }
+        },
+        CueFormat::Markdown => {
+            sheet.push_str("| km | instruction |\n");
+            sheet.push_str("| --: | --- |\n");
+            for cue in cues {
+                sheet.push_str(&format!(
+                    "| {:.2} | {} |\n",
+                    cue.distance / 1000.0,
+                    cue.instruction.replace('|', "\\|")
+                ));
+            }
+        },
+    }
+    sheet
+}
+
+/// Every cue as gpx route point with the instruction as <cmt>
+pub fn cues_to_route(cues: &[Cue]) -> Route {
+    let points = cues
+        .iter()
+        .map(|cue| {
+            let mut waypoint = Waypoint::new(cue.point);
+            waypoint.elevation = cue.elevation;
+            waypoint.name = Some(format!("{:.1} km", cue.distance / 1000.0));
+            waypoint.comment = Some(cue.instruction.clone());
+            waypoint
+        })
+        .collect();
+
+    Route {
+        name: Some(String::from_str("cue sheet").unwrap()),
+        points,
+        ..Default::default()
+    }
+}
+
+/// Instruction for a change of direction by [angle] degrees, None when
+/// going straight on
+fn maneuver(angle: f64) -> Option<String> {
+    let side = if angle > 0.0 { "right" } else { "left" };
+    let angle = angle.abs();
+
+    if angle < STRAIGHT { return None; }
+    if angle < SLIGHT { return Some(format!("bear {}", side)); }
+    if angle > SHARP { return Some(format!("turn sharp {}", side)); }
+    Some(format!("turn {}", side))
+}
+
+/// name or ref of the way, the edge belongs to
+fn street(edge: &GraphEdge) -> String {
+    let tags = edge.tags();
+    match (tags.get("name"), tags.get("ref")) {
+        (Some(name), _) => name.to_string(),
+        (None, Some(r)) => r.to_string(),
+        (None, None) => match tags.get("highway") {
+            Some(highway) => format!("unnamed {}", highway),
+            None => String::from("unnamed way"),
+        },
+    }
+}
+
+fn has_name(edge: &GraphEdge) -> bool {
+    edge.tags().contains_key("name") || edge.tags().contains_key("ref")
+}
+
+fn is_roundabout(edge: &GraphEdge) -> bool {
+    edge.tags().contains("junction", "roundabout")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::Path;
+    use std::collections::HashMap;
+    use geo::Point;
+    use crate::graph::{EdgeId, NodeId, Node as GraphNode};
+
+    fn tags(pairs: &[(&str, &str)]) -> osmpbfreader::Tags {
+        let mut tags = osmpbfreader::Tags::new();
+        for (k, v) in pairs {
+            tags.insert(k.to_string().into(), v.to_string().into());
+        }
+        tags
+    }
+
+    #[test]
+    fn cues_turn_and_street_change() {
+        // 0--->1--->2
+        //           |
+        //           v
+        //           3
+        let mut graph_nodes: HashMap<NodeId, GraphNode> = HashMap::new();
+        graph_nodes.insert(0, GraphNode::new(0, Point::new(7.000, 51.001)));
+        graph_nodes.insert(1, GraphNode::new(1, Point::new(7.001, 51.001)));
+        graph_nodes.insert(2, GraphNode::new(2, Point::new(7.002, 51.001)));
+        graph_nodes.insert(3, GraphNode::new(3, Point::new(7.002, 51.000)));
+
+        let mut graph_edges: HashMap<EdgeId, GraphEdge> = HashMap::new();
+        let streets = [
+            ("Hauptstraße", vec![0, 1]),
+            ("Am Markt", vec![1, 2]),
+            ("Waldweg", vec![2, 3]),
+        ];
+        for (edge_id, (name, nodes)) in streets.into_iter().enumerate() {
+            let edge_id = edge_id as EdgeId;
+            for node_id in nodes.iter() {
+                graph_nodes.get_mut(node_id).unwrap().insert_edge(edge_id);
+            }
+            let mut edge = GraphEdge::new(edge_id, 1.0, false, nodes);
+            edge.set_tags(tags(&[("highway", "residential"), ("name", name)]));
+            graph_edges.insert(edge_id, edge);
+        }
+        let graph = Graph::new(graph_nodes, graph_edges);
+
+        let trip = Trip::new(&graph, &Path::new(vec![0, 1, 2, 3], vec![0, 1, 2]), Vec::new());
+        let cues = trip_to_cues(&graph, &trip);
+        let instructions: Vec<&str> = cues.iter().map(|c| c.instruction.as_str()).collect();
+
+        assert_eq!(vec![
+            "start on Hauptstraße",
+            "continue onto Am Markt",
+            "turn right onto Waldweg",
+            "arrive at the destination",
+        ], instructions);
+
+        let csv = cues_to_string(&cues, &CueFormat::Csv);
+        assert_eq!(Some("0.00,\"start on Hauptstraße\""), csv.lines().nth(1));
+
+        // there and back again ends where it began
+        let trip = Trip::new(&graph, &Path::new(vec![0, 1, 0], vec![0, 0]), Vec::new());
+        let cues = trip_to_cues(&graph, &trip);
+        assert_eq!("arrive at the start", cues.last().unwrap().instruction);
+    }
+}
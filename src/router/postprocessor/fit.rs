@@ -0,0 +1,233 @@
+use time::OffsetDateTime;
+
+use crate::profile::Profile;
+use crate::router::postprocessor::{CoursePointKind, Trip};
+
+// global message numbers of the FIT profile
+const FILE_ID: u16 = 0;
+const LAP: u16 = 19;
+const RECORD: u16 = 20;
+const COURSE: u16 = 31;
+const COURSE_POINT: u16 = 32;
+
+// base types of the FIT protocol
+const ENUM: u8 = 0x00;
+const STRING: u8 = 0x07;
+const UINT16: u8 = 0x84;
+const SINT32: u8 = 0x85;
+const UINT32: u8 = 0x86;
+const UINT32Z: u8 = 0x8C;
+
+/// FIT timestamps count the seconds since 1989-12-31 00:00 UTC
+const FIT_EPOCH: i64 = 631_065_600;
+/// Strings have a fixed length inside of a message, including the trailing 0
+const NAME_LENGTH: usize = 16;
+
+/// FIT course of the [trip], as loaded natively by bike computers. The times
+/// are estimated with the average speed of the [profile]
+pub fn trip_to_fit(trip: &Trip, profile: &Profile) -> Vec<u8> {
+    let start = (OffsetDateTime::now_utc().unix_timestamp() - FIT_EPOCH) as u32;
+    let time_at = |distance: f64| start + (distance / profile.speed()) as u32;
+
+    let points = trip.points();
+    let first = points.first().unwrap();
+    let last = points.last().unwrap();
+    let (ascent, descent) = trip.elevation_gain();
+
+    let mut fit = FitWriter::new();
+
+    // local message type 0
+    fit.define(0, FILE_ID, &[(0, 1, ENUM), (1, 2, UINT16), (2, 2, UINT16), (3, 4, UINT32Z), (4, 4, UINT32)]);
+    let mut message = Vec::new();
+    message.push(6); // course file
+    message.extend(255u16.to_le_bytes()); // manufacturer: development
+    message.extend(0u16.to_le_bytes()); // product
+    message.extend(1u32.to_le_bytes()); // serial number
+    message.extend(start.to_le_bytes());
+    fit.data(0, &message);
+
+    // local message type 1
+    fit.define(1, COURSE, &[(4, 1, ENUM), (5, NAME_LENGTH as u8, STRING)]);
+    let mut message = Vec::new();
+    message.push(2); // sport: cycling
+    message.extend(fixed_string("roundtrip"));
+    fit.data(1, &message);
+
+    // local message type 2
+    fit.define(2, LAP, &[
+        (253, 4, UINT32), (2, 4, UINT32),
+        (3, 4, SINT32), (4, 4, SINT32), (5, 4, SINT32), (6, 4, SINT32),
+        (7, 4, UINT32), (8, 4, UINT32), (9, 4, UINT32),
+        (21, 2, UINT16), (22, 2, UINT16),
+    ]);
+    let duration = (trip.distance() / profile.speed() * 1000.0) as u32;
+    let mut message = Vec::new();
+    message.extend(time_at(trip.distance()).to_le_bytes());
+    message.extend(start.to_le_bytes());
+    message.extend(semicircles(first.point.y()).to_le_bytes());
+    message.extend(semicircles(first.point.x()).to_le_bytes());
+    message.extend(semicircles(last.point.y()).to_le_bytes());
+    message.extend(semicircles(last.point.x()).to_le_bytes());
+    message.extend(duration.to_le_bytes()); // total elapsed time
+    message.extend(duration.to_le_bytes()); // total timer time
+    message.extend(((trip.distance() * 100.0) as u32).to_le_bytes());
+    message.extend((ascent as u16).to_le_bytes());
+    message.extend((descent as u16).to_le_bytes());
+    fit.data(2, &message);
+
+    // local message type 3
+    fit.define(3, RECORD, &[(253, 4, UINT32), (0, 4, SINT32), (1, 4, SINT32), (2, 2, UINT16), (5, 4, UINT32)]);
+    for p in points {
+        let mut message = Vec::new();
+        message.extend(time_at(p.distance).to_le_bytes());
+        message.extend(semicircles(p.point.y()).to_le_bytes());
+        message.extend(semicircles(p.point.x()).to_le_bytes());
+        message.extend(altitude(p.elevation).to_le_bytes());
+        message.extend(((p.distance * 100.0) as u32).to_le_bytes());
+        fit.data(3, &message);
+    }
+
+    // local message type 4
+    fit.define(4, COURSE_POINT, &[
+        (1, 4, UINT32), (2, 4, SINT32), (3, 4, SINT32), (4, 4, UINT32),
+        (5, 1, ENUM), (6, NAME_LENGTH as u8, STRING),
+    ]);
+    for course_point in trip.course_points() {
+        let p = &points[course_point.at];
+        let mut message = Vec::new();
+        message.extend(time_at(p.distance).to_le_bytes());
+        message.extend(semicircles(p.point.y()).to_le_bytes());
+        message.extend(semicircles(p.point.x()).to_le_bytes());
+        message.extend(((p.distance * 100.0) as u32).to_le_bytes());
+        message.push(match course_point.kind {
+            CoursePointKind::Generic => 0,
+            CoursePointKind::Left => 6,
+            CoursePointKind::Right => 7,
+        });
+        message.extend(fixed_string(&course_point.name));
+        fit.data(4, &message);
+    }
+
+    fit.finish()
+}
+
+/// Collects definition and data messages of a FIT file
+struct FitWriter {
+    records: Vec<u8>,
+}
+
+impl FitWriter {
+    fn new() -> Self {
+        Self { records: Vec::new() }
+    }
+
+    /// Announce the [fields] (number, size, base type) of the [global]
+    /// message, following data messages refer to it with the [local] type
+    fn define(&mut self, local: u8, global: u16, fields: &[(u8, u8, u8)]) {
+        self.records.push(0x40 | local);
+        self.records.push(0); // reserved
+        self.records.push(0); // little endian
+        self.records.extend(global.to_le_bytes());
+        self.records.push(fields.len() as u8);
+        for (number, size, base_type) in fields {
+            self.records.extend([*number, *size, *base_type]);
+        }
+    }
+
+    fn data(&mut self, local: u8, fields: &[u8]) {
+        self.records.push(local);
+        self.records.extend(fields);
+    }
+
+    /// File header, all records and the checksum
+    fn finish(self) -> Vec<u8> {
+        let mut file: Vec<u8> = Vec::new();
+        file.push(14); // header size
+        file.push(0x20); // protocol version 2.0
+        file.extend(2132u16.to_le_bytes()); // profile version 21.32
+        file.extend((self.records.len() as u32).to_le_bytes());
+        file.extend(b".FIT");
+        file.extend(crc(&file).to_le_bytes());
+
+        file.extend(self.records);
+        file.extend(crc(&file).to_le_bytes());
+        file
+    }
+}
+
+/// Checksum as defined by the FIT protocol
+fn crc(bytes: &[u8]) -> u16 {
+    const TABLE: [u16; 16] = [
+        0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401,
+        0xA001, 0x6C00, 0x7800, 0xB401, 0x5000, 0x9C01, 0x8801, 0x4400,
+    ];
+
+    let mut crc: u16 = 0;
+    for byte in bytes {
+        // lower nibble
+        let tmp = TABLE[(crc & 0xF) as usize];
+        crc = (crc >> 4) & 0x0FFF;
+        crc = crc ^ tmp ^ TABLE[(byte & 0xF) as usize];
+        // upper nibble
+        let tmp = TABLE[(crc & 0xF) as usize];
+        crc = (crc >> 4) & 0x0FFF;
+        crc = crc ^ tmp ^ TABLE[((byte >> 4) & 0xF) as usize];
+    }
+    crc
+}
+
+/// Degrees as used by FIT, 2^31 semicircles are 180 degrees
+fn semicircles(degrees: f64) -> i32 {
+    (degrees * (2_147_483_648.0 / 180.0)) as i32
+}
+
+/// Altitude with scale 5 and offset 500, 0xFFFF marks an unknown height
+fn altitude(elevation: Option<f64>) -> u16 {
+    match elevation {
+        Some(h) => ((h + 500.0) * 5.0) as u16,
+        None => u16::MAX,
+    }
+}
+
+/// Zero terminated string of fixed length, longer strings are cut
+fn fixed_string(s: &str) -> [u8; NAME_LENGTH] {
+    let mut bytes = [0u8; NAME_LENGTH];
+    let mut length = 0;
+    for c in s.chars() {
+        // multi byte characters must not be cut in half
+        if length + c.len_utf8() > NAME_LENGTH - 1 { break; }
+        c.encode_utf8(&mut bytes[length..]);
+        length += c.len_utf8();
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_file_frame() {
+        let mut fit = FitWriter::new();
+        fit.define(0, COURSE, &[(5, NAME_LENGTH as u8, STRING)]);
+        fit.data(0, &fixed_string("roundtrip"));
+        let file = fit.finish();
+
+        assert_eq!(b".FIT", &file[8..12]);
+        let data_size = u32::from_le_bytes([file[4], file[5], file[6], file[7]]) as usize;
+        assert_eq!(file.len(), 14 + data_size + 2);
+        // a checksum over data and its own checksum is always zero
+        assert_eq!(0, crc(&file[..14]));
+        assert_eq!(0, crc(&file));
+    }
+
+    #[test]
+    fn fit_field_encoding() {
+        assert_eq!(i32::MAX, semicircles(180.0 - 1e-9));
+        assert_eq!(-1_073_741_824, semicircles(-90.0));
+        assert_eq!(2500, altitude(Some(0.0)));
+        assert_eq!(u16::MAX, altitude(None));
+        assert_eq!(b"Gr\xc3\xbcnzug\0\0\0\0\0\0\0\0", &fixed_string("Grünzug"));
+        assert_eq!(0, fixed_string("Kaiser-Wilhelm-Denkmal")[NAME_LENGTH - 1]);
+    }
+}
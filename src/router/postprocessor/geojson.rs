@@ -0,0 +1,138 @@
+use geo::Polygon;
+use serde_json::{json, Value};
+
+use crate::graph::Graph;
+use crate::parser::Poi;
+use crate::router::postprocessor::Trip;
+
+/// FeatureCollection with the [trip] as LineString, its visited pois as
+/// Points and the [hull] the route was planned with as Polygon
+pub fn trip_to_geojson(graph: &Graph, trip: &Trip, hull: Option<&Polygon>) -> String {
+    let mut features: Vec<Value> = Vec::new();
+    features.push(route_feature(graph, trip));
+    features.extend(trip.pois().iter().map(poi_feature));
+    if let Some(hull) = hull {
+        features.push(hull_feature(hull));
+    }
+
+    let collection = json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    serde_json::to_string_pretty(&collection).unwrap()
+}
+
+/// the resolved route, its edges and their surfaces in riding order
+fn route_feature(graph: &Graph, trip: &Trip) -> Value {
+    let coordinates: Vec<Vec<f64>> = trip
+        .points()
+        .iter()
+        .map(|p| {
+            let mut position = vec![p.point.x(), p.point.y()];
+            if let Some(elevation) = p.elevation { position.push(elevation); }
+            position
+        })
+        .collect();
+
+    let surfaces: Vec<String> = trip
+        .segments()
+        .iter()
+        .map(|edge_id| {
+            match graph.edges().get(edge_id).unwrap().tags().get("surface") {
+                Some(surface) => surface.to_string(),
+                None => String::from("unknown"),
+            }
+        })
+        .collect();
+
+    json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "LineString",
+            "coordinates": coordinates,
+        },
+        "properties": {
+            "name": "roundtrip",
+            "distance": trip.distance(),
+            "segments": trip.segments(),
+            "surfaces": surfaces,
+        },
+    })
+}
+
+fn poi_feature(poi: &Poi) -> Value {
+    json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "Point",
+            "coordinates": [poi.point.x(), poi.point.y()],
+        },
+        "properties": {
+            "name": poi.name,
+            "category": poi.category,
+            "url": poi.url(),
+        },
+    })
+}
+
+fn hull_feature(hull: &Polygon) -> Value {
+    let ring: Vec<[f64; 2]> = hull
+        .exterior()
+        .points()
+        .map(|p| [p.x(), p.y()])
+        .collect();
+
+    json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "Polygon",
+            "coordinates": [ring],
+        },
+        "properties": {
+            "name": "concave hull",
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::Path;
+    use std::collections::HashMap;
+    use geo::Point;
+    use crate::graph::{EdgeId, NodeId, Node as GraphNode, Edge as GraphEdge};
+
+    #[test]
+    fn geojson_features() {
+        // 0<-->1<-->2 with the middle node 1 inside of one edge
+        let mut graph_nodes: HashMap<NodeId, GraphNode> = HashMap::new();
+        graph_nodes.insert(0, GraphNode::new(0, Point::new(7.00, 51.0)));
+        graph_nodes.insert(1, GraphNode::new(1, Point::new(7.01, 51.0)));
+        graph_nodes.insert(2, GraphNode::new(2, Point::new(7.02, 51.0)));
+
+        let mut graph_edges: HashMap<EdgeId, GraphEdge> = HashMap::new();
+        graph_edges.insert(7, GraphEdge::new(7, 1.0, false, vec![0, 1, 2]));
+        for node_id in 0..3 { graph_nodes.get_mut(&node_id).unwrap().insert_edge(7); }
+        let graph = Graph::new(graph_nodes, graph_edges);
+
+        let poi = Poi {
+            point: Point::new(7.01, 51.001),
+            name: Some(String::from("Aussicht")),
+            osm_id: osmpbfreader::OsmId::Node(osmpbfreader::NodeId(42)),
+            category: String::from("tourism=viewpoint"),
+        };
+
+        let trip = Trip::new(&graph, &Path::new(vec![0, 2], vec![7]), vec![poi]);
+        let geojson = trip_to_geojson(&graph, &trip, None);
+        let value: Value = serde_json::from_str(&geojson).unwrap();
+        let features = value["features"].as_array().unwrap();
+
+        assert_eq!(2, features.len());
+        assert_eq!("LineString", features[0]["geometry"]["type"]);
+        assert_eq!(3, features[0]["geometry"]["coordinates"].as_array().unwrap().len());
+        assert_eq!(json!([7]), features[0]["properties"]["segments"]);
+        assert_eq!(json!(["unknown"]), features[0]["properties"]["surfaces"]);
+        assert_eq!("Point", features[1]["geometry"]["type"]);
+        assert_eq!("https://www.openstreetmap.org/node/42", features[1]["properties"]["url"]);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::parser::Poi;
+use crate::router::postprocessor::{xml_escape, Trip};
+
+/// KML document with a styled line for the [trip] and a placemark for
+/// every visited poi, e.g. for Google Earth
+pub fn trip_to_kml(trip: &Trip) -> String {
+    let coordinates: Vec<String> = trip
+        .points()
+        .iter()
+        .map(|p| match p.elevation {
+            Some(elevation) => format!("{},{},{}", p.point.x(), p.point.y(), elevation),
+            None => format!("{},{}", p.point.x(), p.point.y()),
+        })
+        .collect();
+
+    let mut kml = String::new();
+    kml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    kml.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n");
+    kml.push_str("<Document>\n");
+    kml.push_str("  <name>roundtrip</name>\n");
+    // colors are noted as aabbggrr
+    kml.push_str("  <Style id=\"route\">\n");
+    kml.push_str("    <LineStyle><color>ff0000e6</color><width>4</width></LineStyle>\n");
+    kml.push_str("  </Style>\n");
+    kml.push_str("  <Style id=\"poi\">\n");
+    kml.push_str("    <IconStyle><color>ff00b4ff</color><scale>1.1</scale><Icon>");
+    kml.push_str("<href>http://maps.google.com/mapfiles/kml/shapes/star.png</href></Icon></IconStyle>\n");
+    kml.push_str("  </Style>\n");
+
+    kml.push_str("  <Placemark>\n");
+    kml.push_str("    <name>roundtrip</name>\n");
+    kml.push_str(&format!("    <description>{:.1} km</description>\n", trip.distance() / 1000.0));
+    kml.push_str("    <styleUrl>#route</styleUrl>\n");
+    kml.push_str("    <LineString><tessellate>1</tessellate><coordinates>\n");
+    kml.push_str(&coordinates.join("\n"));
+    kml.push_str("\n    </coordinates></LineString>\n");
+    kml.push_str("  </Placemark>\n");
+
+    for poi in trip.pois() {
+        kml.push_str(&poi_placemark(poi));
+    }
+
+    kml.push_str("</Document>\n");
+    kml.push_str("</kml>\n");
+    kml
+}
+
+fn poi_placemark(poi: &Poi) -> String {
+    let name = poi.name.clone().unwrap_or(poi.category.clone());
+    format!(
+        "  <Placemark>\n    <name>{}</name>\n    <description>{} {}</description>\n    \
+        <styleUrl>#poi</styleUrl>\n    <Point><coordinates>{},{}</coordinates></Point>\n  </Placemark>\n",
+        xml_escape(&name),
+        xml_escape(&poi.category),
+        xml_escape(&poi.url()),
+        poi.point.x(),
+        poi.point.y()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::Path;
+    use std::collections::HashMap;
+    use geo::Point;
+    use crate::graph::{EdgeId, Graph, NodeId, Node as GraphNode, Edge as GraphEdge};
+
+    #[test]
+    fn kml_placemarks() {
+        // 0<-->1 going uphill
+        let mut graph_nodes: HashMap<NodeId, GraphNode> = HashMap::new();
+        graph_nodes.insert(0, GraphNode::new(0, Point::new(7.25, 51.5)));
+        graph_nodes.insert(1, GraphNode::new(1, Point::new(7.5, 51.5)));
+        graph_nodes.get_mut(&0).unwrap().set_elevation(120.0);
+        graph_nodes.get_mut(&1).unwrap().set_elevation(135.5);
+
+        let mut graph_edges: HashMap<EdgeId, GraphEdge> = HashMap::new();
+        graph_edges.insert(0, GraphEdge::new(0, 1.0, false, vec![0, 1]));
+        graph_nodes.get_mut(&0).unwrap().insert_edge(0);
+        graph_nodes.get_mut(&1).unwrap().insert_edge(0);
+        let graph = Graph::new(graph_nodes, graph_edges);
+
+        let pois = vec![
+            Poi {
+                point: Point::new(7.3, 51.5),
+                name: Some(String::from("Café & Bar <Zur Post>")),
+                osm_id: osmpbfreader::OsmId::Node(osmpbfreader::NodeId(42)),
+                category: String::from("amenity=cafe"),
+            },
+            Poi {
+                point: Point::new(7.4, 51.5),
+                name: None,
+                osm_id: osmpbfreader::OsmId::Node(osmpbfreader::NodeId(43)),
+                category: String::from("tourism=viewpoint"),
+            },
+        ];
+        let trip = Trip::new(&graph, &Path::new(vec![0, 1], vec![0]), pois);
+        let kml = trip_to_kml(&trip);
+
+        // the line and one per poi
+        assert_eq!(3, kml.matches("<Placemark>").count());
+        // longitude first, then latitude and elevation
+        assert!(kml.contains("7.25,51.5,120\n7.5,51.5,135.5"));
+        assert!(kml.contains("<Point><coordinates>7.3,51.5</coordinates></Point>"));
+        assert!(kml.contains("<name>Café &amp; Bar &lt;Zur Post&gt;</name>"));
+        assert!(kml.contains("<name>tourism=viewpoint</name>"));
+    }
+}
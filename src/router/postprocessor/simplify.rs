@@ -0,0 +1,160 @@
+use geo::{Coord, LineString, SimplifyIdx, SimplifyVwIdx};
+
+use crate::router::postprocessor::{Trip, TripPoint};
+
+/// Meters per degree of latitude
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Algorithm to thin out the points of a trip
+#[derive(serde::Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Simplification {
+    /// Ramer–Douglas–Peucker, keeps points further away than the tolerance
+    #[default]
+    Rdp,
+    /// Visvalingam–Whyatt, keeps points spanning a triangle larger than
+    /// the tolerance squared
+    Visvalingam,
+}
+
+impl Trip {
+    /// Drop points, that deviate less than [tolerance] meters from the
+    /// remaining line. With [max_points] the tolerance is raised until the
+    /// trip fits. Start, end, decision points and pois are always kept, so
+    /// there may be more points than wished for. Returns the kept points
+    pub fn simplify(&mut self, method: &Simplification, tolerance: f64, max_points: Option<usize>) -> usize {
+        let required = self.required_points();
+
+        let mut tolerance = tolerance;
+        let mut keep = simplified(&self.points, &required, method, tolerance);
+        if let Some(max_points) = max_points {
+            // the tolerance is at least one meter, otherwise doubling is endless
+            tolerance = tolerance.max(1.0);
+            while keep.len() > max_points && keep.len() > required.len() {
+                tolerance *= 2.0;
+                keep = simplified(&self.points, &required, method, tolerance);
+            }
+        }
+
+        // main nodes, that got dropped, move to the previous remaining point
+        self.at = self.at
+            .iter()
+            .map(|i| keep.iter().rposition(|k| k <= i).unwrap())
+            .collect();
+
+        let mut points = std::mem::take(&mut self.points);
+        let mut position = 0;
+        points.retain(|_| {
+            position += 1;
+            keep.binary_search(&(position - 1)).is_ok()
+        });
+        self.points = points;
+        self.points.len()
+    }
+
+    /// Positions, that must not be removed by the simplification, ascending
+    fn required_points(&self) -> Vec<usize> {
+        let mut required: Vec<usize> = self.decision_points();
+        required.push(0);
+        required.push(self.points.len() - 1);
+        for poi in self.pois.iter() {
+            required.push(self.nearest(&poi.point));
+        }
+        required.sort();
+        required.dedup();
+        required
+    }
+}
+
+/// Positions of the [points] that remain, the [required] ones separate the
+/// trip into pieces, which are simplified on their own
+fn simplified(points: &[TripPoint], required: &[usize], method: &Simplification, tolerance: f64) -> Vec<usize> {
+    let mut keep: Vec<usize> = vec![0];
+    for piece in required.windows(2) {
+        let (from, to) = (piece[0], piece[1]);
+        let line = to_meters(&points[from..=to]);
+        let indices = match method {
+            Simplification::Rdp => line.simplify_idx(&tolerance),
+            Simplification::Visvalingam => line.simplify_vw_idx(&(tolerance * tolerance)),
+        };
+        // the first point of the piece is the last of the previous one
+        keep.extend(indices.iter().skip(1).map(|i| from + i));
+    }
+    keep
+}
+
+/// Local planar coordinates in meters, precise enough for tolerances of
+/// a few meters
+fn to_meters(points: &[TripPoint]) -> LineString {
+    let scale = points[0].point.y().to_radians().cos();
+    points
+        .iter()
+        .map(|p| Coord {
+            x: p.point.x() * scale * METERS_PER_DEGREE,
+            y: p.point.y() * METERS_PER_DEGREE,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::Point;
+    use crate::parser::Poi;
+
+    fn trip(coordinates: &[(f64, f64)], pois: Vec<Poi>) -> Trip {
+        let points = coordinates
+            .iter()
+            .map(|(x, y)| TripPoint { point: Point::new(*x, *y), elevation: None, distance: 0.0 })
+            .collect();
+        Trip {
+            points,
+            main_nodes: vec![0, 1],
+            at: vec![0, coordinates.len() - 1],
+            segments: Vec::new(),
+            pois,
+        }
+    }
+
+    #[test]
+    fn simplify_keeps_pois() {
+        // almost straight line with a wobble of about 1 m at every point
+        let coordinates: Vec<(f64, f64)> = (0..10)
+            .map(|i| (7.0 + i as f64 * 0.001, 51.0 + (i % 2) as f64 * 0.00001))
+            .collect();
+
+        let mut straight = trip(&coordinates, Vec::new());
+        straight.simplify(&Simplification::Rdp, 5.0, None);
+        assert_eq!(2, straight.points().len());
+
+        let poi = Poi {
+            point: Point::new(7.004, 51.001),
+            name: None,
+            osm_id: osmpbfreader::OsmId::Node(osmpbfreader::NodeId(1)),
+            category: String::from("tourism=viewpoint"),
+        };
+        let mut with_poi = trip(&coordinates, vec![poi]);
+        with_poi.simplify(&Simplification::Visvalingam, 30.0, None);
+        let points: Vec<Point> = with_poi.points().iter().map(|p| p.point).collect();
+        assert_eq!(vec![
+            Point::new(7.0, 51.0),
+            Point::new(7.004, 51.0),
+            Point::new(7.009, 51.00001),
+        ], points);
+        assert_eq!(&vec![0, 2], with_poi.main_node_positions());
+    }
+
+    #[test]
+    fn simplify_to_max_points() {
+        // zig zag with 11 m amplitude, too gentle for decision points
+        let coordinates: Vec<(f64, f64)> = (0..21)
+            .map(|i| (7.0 + i as f64 * 0.001, 51.0 + (i % 2) as f64 * 0.0001))
+            .collect();
+
+        let mut zig_zag = trip(&coordinates, Vec::new());
+        let kept = zig_zag.simplify(&Simplification::Rdp, 0.0, Some(5));
+        assert!(kept <= 5);
+        assert_eq!(kept, zig_zag.points().len());
+        assert_eq!(Point::new(7.02, 51.0), zig_zag.points().last().unwrap().point);
+    }
+}
@@ -0,0 +1,106 @@
+use time::{Duration, OffsetDateTime};
+use time::format_description::well_known::Rfc3339;
+
+use crate::profile::Profile;
+use crate::router::postprocessor::{xml_escape, Trip};
+
+/// Garmin devices show only the first characters of a course point name
+const MAX_NAME_LENGTH: usize = 10;
+
+/// TCX course of the [trip]. The times are estimated with the average speed
+/// of the [profile], course points mark turns and visited pois
+pub fn trip_to_tcx(trip: &Trip, profile: &Profile) -> String {
+    let start = OffsetDateTime::now_utc();
+    let time_at = |distance: f64| -> String {
+        let seconds = distance / profile.speed();
+        (start + Duration::seconds_f64(seconds)).format(&Rfc3339).unwrap()
+    };
+
+    let points = trip.points();
+    let first = points.first().unwrap();
+    let last = points.last().unwrap();
+
+    let mut tcx = String::new();
+    tcx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    tcx.push_str("<TrainingCenterDatabase xmlns=\"http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2\">\n");
+    tcx.push_str("<Courses>\n<Course>\n");
+    tcx.push_str("  <Name>roundtrip</Name>\n");
+
+    tcx.push_str("  <Lap>\n");
+    tcx.push_str(&format!("    <TotalTimeSeconds>{:.0}</TotalTimeSeconds>\n", trip.distance() / profile.speed()));
+    tcx.push_str(&format!("    <DistanceMeters>{:.1}</DistanceMeters>\n", trip.distance()));
+    tcx.push_str(&format!("    <BeginPosition>{}</BeginPosition>\n", position(first.point)));
+    tcx.push_str(&format!("    <EndPosition>{}</EndPosition>\n", position(last.point)));
+    tcx.push_str("    <Intensity>Active</Intensity>\n");
+    tcx.push_str("  </Lap>\n");
+
+    tcx.push_str("  <Track>\n");
+    for p in points {
+        tcx.push_str("    <Trackpoint>");
+        tcx.push_str(&format!("<Time>{}</Time>", time_at(p.distance)));
+        tcx.push_str(&format!("<Position>{}</Position>", position(p.point)));
+        if let Some(elevation) = p.elevation {
+            tcx.push_str(&format!("<AltitudeMeters>{:.1}</AltitudeMeters>", elevation));
+        }
+        tcx.push_str(&format!("<DistanceMeters>{:.1}</DistanceMeters>", p.distance));
+        tcx.push_str("</Trackpoint>\n");
+    }
+    tcx.push_str("  </Track>\n");
+
+    for course_point in trip.course_points() {
+        let p = &points[course_point.at];
+        let name: String = course_point.name.chars().take(MAX_NAME_LENGTH).collect();
+        tcx.push_str("  <CoursePoint>");
+        tcx.push_str(&format!("<Name>{}</Name>", xml_escape(&name)));
+        tcx.push_str(&format!("<Time>{}</Time>", time_at(p.distance)));
+        tcx.push_str(&format!("<Position>{}</Position>", position(p.point)));
+        tcx.push_str(&format!("<PointType>{}</PointType>", course_point.kind.name()));
+        tcx.push_str(&format!("<Notes>{}</Notes>", xml_escape(&course_point.notes)));
+        tcx.push_str("</CoursePoint>\n");
+    }
+
+    tcx.push_str("</Course>\n</Courses>\n");
+    tcx.push_str("</TrainingCenterDatabase>\n");
+    tcx
+}
+
+fn position(p: geo::Point) -> String {
+    format!("<LatitudeDegrees>{}</LatitudeDegrees><LongitudeDegrees>{}</LongitudeDegrees>", p.y(), p.x())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::Path;
+    use std::collections::HashMap;
+    use geo::Point;
+    use crate::graph::{EdgeId, Graph, NodeId, Node as GraphNode, Edge as GraphEdge};
+
+    #[test]
+    fn tcx_course_points_at_turns() {
+        // 0--->1
+        //      |
+        //      v
+        //      2
+        let mut graph_nodes: HashMap<NodeId, GraphNode> = HashMap::new();
+        graph_nodes.insert(0, GraphNode::new(0, Point::new(7.000, 51.001)));
+        graph_nodes.insert(1, GraphNode::new(1, Point::new(7.001, 51.001)));
+        graph_nodes.insert(2, GraphNode::new(2, Point::new(7.001, 51.000)));
+
+        let mut graph_edges: HashMap<EdgeId, GraphEdge> = HashMap::new();
+        graph_edges.insert(0, GraphEdge::new(0, 1.0, false, vec![0, 1]));
+        graph_nodes.get_mut(&0).unwrap().insert_edge(0);
+        graph_nodes.get_mut(&1).unwrap().insert_edge(0);
+        graph_edges.insert(1, GraphEdge::new(1, 1.0, false, vec![1, 2]));
+        graph_nodes.get_mut(&1).unwrap().insert_edge(1);
+        graph_nodes.get_mut(&2).unwrap().insert_edge(1);
+        let graph = Graph::new(graph_nodes, graph_edges);
+
+        let trip = Trip::new(&graph, &Path::new(vec![0, 1, 2], vec![0, 1]), Vec::new());
+        let tcx = trip_to_tcx(&trip, &Profile::Trekking);
+
+        assert_eq!(3, tcx.matches("<Trackpoint>").count());
+        assert_eq!(1, tcx.matches("<CoursePoint>").count());
+        assert!(tcx.contains("<PointType>Right</PointType>"));
+    }
+}
@@ -1,90 +1,180 @@
+use std::cmp::Reverse;
+
 use geo::ConcaveHull;
+use geo::{HaversineBearing, HaversineDistance};
 use geo::LineString;
+use geo::winding_order::{Winding, WindingOrder};
 use geo::Point;
+use geo::Polygon;
+use ordered_float::NotNan;
+use priority_queue::PriorityQueue;
 
 use crate::graph::{Graph, NodeId};
 
-/// orders interesting nodes based on their location in a concave hull
-/// note: the inner nodes of a the hull are not used yet
-pub fn order_with_concave_hull(graph: &Graph, start: &NodeId, visit: &mut Vec<NodeId>) -> Vec<NodeId> {   
+/// Way round, a loop is ridden, as seen on a map with north up
+#[derive(serde::Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Turns are mostly to the right, which is easier in right-hand traffic
+    #[default]
+    Clockwise,
+    Counterclockwise,
+}
+
+impl Direction {
+    /// Way round the loop from [start] through the [waypoints] goes, None if
+    /// it encloses no area
+    pub fn of(graph: &Graph, start: &NodeId, waypoints: &[NodeId]) -> Option<Self> {
+        let ring: LineString = std::iter::once(start)
+            .chain(waypoints.iter())
+            .chain(std::iter::once(start))
+            .map(|node_id| *graph.nodes().get(node_id).unwrap().point())
+            .collect();
+        match ring.winding_order()? {
+            WindingOrder::Clockwise => Some(Self::Clockwise),
+            WindingOrder::CounterClockwise => Some(Self::Counterclockwise),
+        }
+    }
+
+    fn winding_order(&self) -> WindingOrder {
+        match self {
+            Self::Clockwise => WindingOrder::Clockwise,
+            Self::Counterclockwise => WindingOrder::CounterClockwise,
+        }
+    }
+}
+
+/// orders interesting nodes based on their location in a concave hull.
+/// Nodes inside of the hull are put in by their bearing from the start: the
+/// ones in the sector, the loop leaves to, come first (nearest first), the
+/// ones in the sector, it returns from, come last (farthest first) and all
+/// others are inserted between the hull nodes of similar bearing
+pub fn order_with_concave_hull(graph: &Graph, start: &NodeId, visit: &mut Vec<NodeId>, direction: &Direction) -> Vec<NodeId> {   
     let mut points_with_ids: Vec<GraphPoint> = Vec::new();
     
     let mut points: Vec<Point> = Vec::new();
 
     for node_id in visit {
         let point = graph.nodes().get(node_id).unwrap().point();
+        // several pois may share their closest node
+        if points.contains(point) { continue; }
         points_with_ids.push(GraphPoint { point: *point, id: *node_id  });
         points.push(*point);
     }
+    // there is no hull around less than three points
+    if points.len() < 3 {
+        return points_with_ids.iter().map(|gp| gp.id).collect();
+    }
 
-    let ls = LineString::from(points);
-
-    // note: concavity factor could be radius dependend
-    // note: concave hull is not sorted
-    let hull = ls.concave_hull(3.0);
-    // let hull = geo::algorithm::ConvexHull::convex_hull(&ls);
+    let ls = LineString::from(points.clone());
+    let hull = concave_hull(&ls);
 
     let mut ring: Vec<Point> = hull.exterior().points().collect();
     ring.dedup();
+    // the exterior is closed, its last point is the first one
+    if ring.len() > 1 && ring.first() == ring.last() { ring.pop(); }
 
-    // note: nodes inside the hull are discarded for now
-    // let mut inner: Vec<Point> = ls.points().filter(|p| ! ring.contains(p)).collect();
-    // inner.dedup();
-     
-    
-    // let start_point = graph.nodes().get(start).unwrap().point();
-    // let bearing_first = geo::HaversineBearing::haversine_bearing(start_point, *ring.first().unwrap());
-    // let bearing_last = geo::HaversineBearing::haversine_bearing(start_point, *ring.last().unwrap());
-
-    // let quadrant_first = Quadrant::to_quadrant(&bearing_first.to_degrees());
-    // let mut quadrant_last = Quadrant::to_quadrant(&bearing_last.to_degrees());
-
-    // if quadrant_first == quadrant_last {
-    //     // quadrants are identical, choose neighbouring quadrant for return back to start
-    //     let bearing_diff = bearing_first.to_degrees() - bearing_last.to_degrees();
-
-    //     if bearing_diff < 0.0 {
-    //         // last has a higher bearing than first
-    //         // => (rotate clockwise to the next quadrant)
-    //         quadrant_last = quadrant_last.neighbour_cw();
-    //     }
-    //     else {
-    //         // last has a lower bearing than first
-    //         // => (rotate counterclockwise to the previous quadrant)
-    //         quadrant_last = quadrant_last.neighbour_ccw();
-    //     }
-    // }
- 
-    // // smallest distance point from start is always first (nearest from start of route)
-    // let mut before_first: PriorityQueue<NodeId, Reverse<NotNan<f64>>> = PriorityQueue::new();
-    // // largest distance point from start is always first (nearest to end of ring)
-    // let mut after_last: PriorityQueue<NodeId, NotNan<f64>> = PriorityQueue::new();
-    // // note: could be made more efficient by removing the points from inner
-    // for point in inner {
-    //     let bearing = geo::HaversineBearing::haversine_bearing(start_point, point);
-    //     let quadrant = Quadrant::to_quadrant(&bearing.to_degrees());
-        
-    //     if quadrant == quadrant_first || quadrant == quadrant_last {
-    //         // get node ids
-    //         let node_id = back_to_id(&points_with_ids, &point);           
-    //         let distance = geo::algorithm::HaversineDistance::haversine_distance(start_point, &point);
-
-    //         if quadrant == quadrant_first {
-    //             before_first.push(node_id, Reverse(NotNan::new(distance).unwrap()));  
-    //         } else  {
-    //             after_last.push(node_id, NotNan::new(distance).unwrap());
-    //         }
-    //     }
-    // }  
+    let start_point = *graph.nodes().get(start).unwrap().point();
+    let bearing = |p: &Point| start_point.haversine_bearing(*p).rem_euclid(360.0);
+
+    // ride around the hull in the wished direction, beginning at the hull
+    // point closest to the start, so the first part of the route is short
+    let closed: LineString = ring.iter().chain(ring.first()).copied().collect();
+    if closed.winding_order() != Some(direction.winding_order()) { ring.reverse(); }
+    let nearest = (0..ring.len())
+        .min_by(|a, b| start_point.haversine_distance(&ring[*a]).total_cmp(&start_point.haversine_distance(&ring[*b])))
+        .unwrap();
+    ring.rotate_left(nearest);
+
+    let inner: Vec<Point> = points.iter().filter(|p| ! ring.contains(p)).copied().collect();
+
+    let bearing_first = bearing(ring.first().unwrap());
+    let bearing_last = bearing(ring.last().unwrap());
+
+    let quadrant_first = Quadrant::to_quadrant(&bearing_first);
+    let mut quadrant_last = Quadrant::to_quadrant(&bearing_last);
+
+    if quadrant_first == quadrant_last {
+        // quadrants are identical, choose neighbouring quadrant for return back to start
+        let bearing_diff = bearing_first - bearing_last;
+
+        if bearing_diff < 0.0 {
+            // last has a higher bearing than first
+            // => (rotate clockwise to the next quadrant)
+            quadrant_last = quadrant_last.neighbour_cw();
+        }
+        else {
+            // last has a lower bearing than first
+            // => (rotate counterclockwise to the previous quadrant)
+            quadrant_last = quadrant_last.neighbour_ccw();
+        }
+    }
+
+    // smallest distance point from start is always first (nearest from start of route)
+    let mut before_first: PriorityQueue<NodeId, Reverse<NotNan<f64>>> = PriorityQueue::new();
+    // largest distance point from start is always first (nearest to end of ring)
+    let mut after_last: PriorityQueue<NodeId, NotNan<f64>> = PriorityQueue::new();
+    for point in inner {
+        let quadrant = Quadrant::to_quadrant(&bearing(&point));
+        let node_id = back_to_id(&points_with_ids, &point);
+        let distance = NotNan::new(start_point.haversine_distance(&point)).unwrap();
+
+        if quadrant == quadrant_first {
+            before_first.push(node_id, Reverse(distance));
+        } else if quadrant == quadrant_last {
+            after_last.push(node_id, distance);
+        } else {
+            // between the hull points, where the bearing changes the least
+            let position = (1..ring.len())
+                .min_by(|a, b| {
+                    let detour = |i: &usize| {
+                        let (before, after) = (bearing(&ring[i - 1]), bearing(&ring[*i]));
+                        angle_between(before, bearing(&point)) + angle_between(bearing(&point), after) - angle_between(before, after)
+                    };
+                    detour(a).total_cmp(&detour(b))
+                })
+                .unwrap();
+            ring.insert(position, point);
+        }
+    }
 
     let mut result: Vec<NodeId> = Vec::new();
-    // result.append(&mut before_first.into_sorted_vec());
+    result.append(&mut before_first.into_sorted_vec());
     result.append(&mut ring.iter().map(|p| back_to_id(&points_with_ids, p)).collect());
-    // result.append(&mut after_last.into_sorted_vec());
+    result.append(&mut after_last.into_sorted_vec());
      
     result
 }
 
+/// orders interesting nodes for a trip from [start] to [end] by how much
+/// closer to the start than to the end they are, duplicates are dropped
+pub fn order_along(graph: &Graph, start: &NodeId, end: &NodeId, visit: &[NodeId]) -> Vec<NodeId> {
+    let point = |node_id: &NodeId| *graph.nodes().get(node_id).unwrap().point();
+    let progress = |node_id: &NodeId| point(start).haversine_distance(&point(node_id)) - point(node_id).haversine_distance(&point(end));
+
+    let mut ordered: Vec<NodeId> = visit.to_vec();
+    ordered.sort_by(|a, b| progress(a).total_cmp(&progress(b)));
+    ordered.dedup();
+    ordered.retain(|node_id| node_id != start && node_id != end);
+    ordered
+}
+
+/// Concave hull around the points of the nodes to visit
+pub fn concave_hull_of(graph: &Graph, visit: &[NodeId]) -> Polygon {
+    let ls: LineString = visit
+        .iter()
+        .map(|node_id| *graph.nodes().get(node_id).unwrap().point())
+        .collect();
+    concave_hull(&ls)
+}
+
+fn concave_hull(ls: &LineString) -> Polygon {
+    // note: concavity factor could be radius dependend
+    // note: concave hull is not sorted
+    ls.concave_hull(3.0)
+    // geo::algorithm::ConvexHull::convex_hull(ls)
+}
+
 /// used to reidentify Points with their corresponding graph nodes
 /// note: symptom of the demand give easy access of Points via references to
 ///       [GraphNode]s instead of just passing around [NodeId]s
@@ -105,6 +195,14 @@ fn back_to_id(v: &Vec<GraphPoint>, point: &Point) -> NodeId {
     }).unwrap()
 }
 
+/// Smaller difference in degrees between two bearings
+fn angle_between(a: f64, b: f64) -> f64 {
+    let difference = (a - b).rem_euclid(360.0);
+    difference.min(360.0 - difference)
+}
+
+/// Sectors of bearings, seen from the start
+#[derive(Debug, Clone, Copy)]
 enum Quadrant {
     NE, SE, SW, NW
 }
@@ -152,3 +250,103 @@ impl PartialEq for Quadrant {
         self.value().0 == other.value().0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::graph::Node;
+
+    /// Graph of unconnected nodes, node 0 is the start at the center
+    fn around_start(offsets: &[(f64, f64)]) -> Graph {
+        let mut nodes: HashMap<NodeId, Node> = HashMap::new();
+        nodes.insert(0, Node::new(0, Point::new(7.0, 51.0)));
+        for (i, (x, y)) in offsets.iter().enumerate() {
+            let node_id = i as NodeId + 1;
+            nodes.insert(node_id, Node::new(node_id, Point::new(7.0 + x, 51.0 + y)));
+        }
+        Graph::new(nodes, HashMap::new())
+    }
+
+    fn bearing(graph: &Graph, node_id: &NodeId) -> f64 {
+        let start = graph.nodes().get(&0).unwrap().point();
+        start.haversine_bearing(*graph.nodes().get(node_id).unwrap().point()).rem_euclid(360.0)
+    }
+
+    #[test]
+    fn inner_points_are_kept_in_order() {
+        // eight points on a circle and four closer to the start
+        let mut offsets: Vec<(f64, f64)> = (0..8)
+            .map(|i| (i as f64 * 45.0_f64).to_radians())
+            .map(|angle| (angle.sin() * 0.016, angle.cos() * 0.01))
+            .collect();
+        offsets.extend([(0.004, 0.002), (0.005, -0.001), (-0.003, -0.004), (-0.004, 0.003)]);
+        let graph = around_start(&offsets);
+
+        let mut visit: Vec<NodeId> = (1..=12).collect();
+        // the same node twice is visited once
+        visit.push(3);
+        let ordered = order_with_concave_hull(&graph, &0, &mut visit, &Direction::Clockwise);
+
+        let mut sorted = ordered.clone();
+        sorted.sort();
+        assert_eq!((1..=12).collect::<Vec<NodeId>>(), sorted);
+
+        // no jumps across the start
+        for pair in ordered.windows(2) {
+            let jump = angle_between(bearing(&graph, &pair[0]), bearing(&graph, &pair[1]));
+            assert!(jump <= 90.0, "{:?} jumps {} degrees", pair, jump);
+        }
+
+        // leaves and returns in the same or in neighbouring sectors
+        let first = Quadrant::to_quadrant(&bearing(&graph, ordered.first().unwrap()));
+        let last = Quadrant::to_quadrant(&bearing(&graph, ordered.last().unwrap()));
+        assert!(first == last || first.neighbour_cw() == last || first.neighbour_ccw() == last);
+    }
+
+    #[test]
+    fn order_along_from_start_to_end() {
+        // the end 1 is to the east, the others lie on the way or aside
+        let graph = around_start(&[(0.02, 0.0), (0.015, 0.005), (0.005, -0.004), (0.01, 0.008)]);
+        let ordered = order_along(&graph, &0, &1, &[4, 2, 3, 1, 2, 3]);
+        assert_eq!(vec![3, 4, 2], ordered);
+    }
+
+    #[test]
+    fn ring_begins_near_start() {
+        // eight points on a circle, the one to the east is closer
+        let offsets: Vec<(f64, f64)> = (0..8)
+            .map(|i| {
+                let angle = (i as f64 * 45.0_f64).to_radians();
+                let radius = if i == 2 { 0.008 } else { 0.01 };
+                (angle.sin() * radius * 1.6, angle.cos() * radius)
+            })
+            .collect();
+        let graph = around_start(&offsets);
+        let mut visit: Vec<NodeId> = (1..=8).collect();
+
+        // north is node 1, east is node 3
+        let clockwise = order_with_concave_hull(&graph, &0, &mut visit, &Direction::Clockwise);
+        assert_eq!(vec![3, 4, 5, 6, 7, 8, 1, 2], clockwise);
+        assert_eq!(Some(Direction::Clockwise), Direction::of(&graph, &0, &clockwise[1..]));
+
+        let counterclockwise = order_with_concave_hull(&graph, &0, &mut visit, &Direction::Counterclockwise);
+        assert_eq!(vec![3, 2, 1, 8, 7, 6, 5, 4], counterclockwise);
+        assert_eq!(Some(Direction::Counterclockwise), Direction::of(&graph, &0, &counterclockwise[1..]));
+        assert_eq!(None, Direction::of(&graph, &0, &[3]));
+    }
+
+    #[test]
+    fn few_points_without_hull() {
+        let graph = around_start(&[(0.01, 0.0), (0.0, 0.01)]);
+        assert_eq!(vec![2, 1], order_with_concave_hull(&graph, &0, &mut vec![2, 1, 2], &Direction::Clockwise));
+    }
+
+    #[test]
+    fn quadrant_neighbours() {
+        assert_eq!(Quadrant::SE, Quadrant::to_quadrant(&135.0));
+        assert_eq!(Quadrant::NW, Quadrant::NE.neighbour_ccw());
+        assert_eq!(Quadrant::NE, Quadrant::NW.neighbour_cw());
+        assert_eq!(10.0, angle_between(355.0, 5.0));
+    }
+}
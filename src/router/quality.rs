@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+use geo::{HaversineBearing, Point};
+
+use crate::graph::{EdgeId, Graph, NodeId};
+use crate::router::{a_star, Path, Weighting};
+use crate::router::weighting::Overlap;
+
+/// Rounds of analysing and repairing a route
+const MAX_REPAIRS: usize = 20;
+
+/// Part of a route from one of its node positions to a later one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Section {
+    pub from: usize,
+    pub to: usize,
+}
+
+/// Something, that makes a loop less nice to ride
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flaw {
+    /// Edges ridden once more in the same direction
+    Repeated(Section),
+    /// Edges ridden back right after riding them, e.g. out to a dead end
+    Spur(Section),
+    /// Edges ridden back later on, like the stick of a lollipop
+    Lollipop(Section),
+    /// The route passes a node twice and crosses its own way there (figure
+    /// eight), the section is the part in between
+    Crossing(Section),
+}
+
+/// Every flaw of the [route]. Sections of ridden edges cover the second
+/// ride, where they can be replaced
+pub fn analyse(graph: &Graph, route: &Path) -> Vec<Flaw> {
+    let mut flaws = repetitions(graph, route);
+    flaws.append(&mut crossings(graph, route));
+    flaws
+}
+
+/// Replace the flawed sections of the [route]: repetitions are rerouted
+/// between their ends with every other edge of the route penalised and the
+/// loop of a figure eight is ridden the other way around. A change is only
+/// kept, if it makes the route less repetitive or crossing without getting
+/// longer than [max_length] meters
+pub fn repair(graph: &Graph, weighting: &Weighting, route: &Path, max_length: f64) -> Path {
+    let mut route = route.clone();
+    let mut current = badness(graph, &route);
+
+    for _ in 0..MAX_REPAIRS {
+        let mut repaired = false;
+        for flaw in analyse(graph, &route) {
+            let candidate = match flaw {
+                Flaw::Repeated(section) | Flaw::Spur(section) | Flaw::Lollipop(section) =>
+                    reroute(graph, weighting, &route, &section),
+                Flaw::Crossing(section) => reverse(graph, &route, &section),
+            };
+            let candidate = match candidate {
+                Some(c) => c,
+                None => continue,
+            };
+
+            let improved = badness(graph, &candidate);
+            if length(graph, &candidate) <= max_length.max(length(graph, &route))
+                && improved.0.total_cmp(&current.0).then(improved.1.cmp(&current.1)).is_lt() {
+                route = candidate;
+                current = improved;
+                repaired = true;
+                // positions of the remaining flaws have changed
+                break;
+            }
+        }
+        if ! repaired { break; }
+    }
+
+    route
+}
+
+/// Meters ridden more than once and the number of crossings
+fn badness(graph: &Graph, route: &Path) -> (f64, usize) {
+    let mut seen: HashMap<EdgeId, usize> = HashMap::new();
+    let mut repeated = 0.0;
+    for edge_id in route.edges() {
+        let count = seen.entry(*edge_id).or_insert(0);
+        *count += 1;
+        if *count > 1 { repeated += graph.edges().get(edge_id).unwrap().distance(); }
+    }
+    (repeated, crossings(graph, route).len())
+}
+
+fn length(graph: &Graph, route: &Path) -> f64 {
+    route.edges().iter().map(|edge_id| graph.edges().get(edge_id).unwrap().distance()).sum()
+}
+
+/// Whether the edge at position [i] of the [route] is ridden s-->t
+fn is_forward(graph: &Graph, route: &Path, i: usize) -> bool {
+    graph.edges().get(&route.edges()[i]).unwrap().s() == &route.nodes()[i]
+}
+
+/// Runs of edges, that have been ridden before, classified by direction
+fn repetitions(graph: &Graph, route: &Path) -> Vec<Flaw> {
+    let edges = route.edges();
+    // direction of the first ride of every edge
+    let mut ridden: HashMap<EdgeId, bool> = HashMap::new();
+    let mut flaws = Vec::new();
+
+    let mut i = 0;
+    while i < edges.len() {
+        let forward = match ridden.get(&edges[i]) {
+            Some(f) => *f,
+            None => {
+                ridden.insert(edges[i], is_forward(graph, route, i));
+                i += 1;
+                continue;
+            }
+        };
+        let opposite = forward != is_forward(graph, route, i);
+
+        // extend the run as long as the edges are known and keep the direction
+        let from = i;
+        while i < edges.len()
+            && ridden.get(&edges[i]).is_some_and(|f| (*f != is_forward(graph, route, i)) == opposite) {
+            i += 1;
+        }
+        let section = Section { from, to: i };
+
+        flaws.push(match opposite {
+            false => Flaw::Repeated(section),
+            true if from > 0 && edges[from - 1] == edges[from] => Flaw::Spur(section),
+            true => Flaw::Lollipop(section),
+        });
+    }
+
+    flaws
+}
+
+/// Nodes passed twice, where the two rides cross each other
+fn crossings(graph: &Graph, route: &Path) -> Vec<Flaw> {
+    let nodes = route.nodes();
+    let mut positions: HashMap<NodeId, Vec<usize>> = HashMap::new();
+    // start and end are no passes
+    for (i, node_id) in nodes.iter().enumerate().take(nodes.len().saturating_sub(1)).skip(1) {
+        positions.entry(*node_id).or_default().push(i);
+    }
+
+    let mut flaws = Vec::new();
+    for passes in positions.values() {
+        for (a, first) in passes.iter().enumerate() {
+            for second in passes.iter().skip(a + 1) {
+                if crosses(graph, route, *first, *second) {
+                    flaws.push(Flaw::Crossing(Section { from: *first, to: *second }));
+                }
+            }
+        }
+    }
+    flaws.sort_by_key(|flaw| match flaw { Flaw::Crossing(s) => s.from, _ => 0 });
+    flaws
+}
+
+/// Whether the ride through the node at position [first] and the one at
+/// [second] cross, i.e. the second ride enters on one side of the first
+/// ride and leaves on the other side
+fn crosses(graph: &Graph, route: &Path, first: usize, second: usize) -> bool {
+    let bearing = |i: usize, edge: usize| {
+        let node = graph.nodes().get(&route.nodes()[i]).unwrap().point();
+        node.haversine_bearing(next_point(graph, &route.edges()[edge], &route.nodes()[i]))
+    };
+    let (a, b) = (bearing(first, first - 1), bearing(first, first));
+    let (c, d) = (bearing(second, second - 1), bearing(second, second));
+
+    // the rides share a road, this is a repetition
+    let shared = [a, b].iter().any(|x| (x - c).abs() < 1e-9 || (x - d).abs() < 1e-9);
+    if shared { return false; }
+
+    between(c, a, b) != between(d, a, b)
+}
+
+/// The point next to [node_id] along the edge
+fn next_point(graph: &Graph, edge_id: &EdgeId, node_id: &NodeId) -> Point {
+    let nodes = graph.edges().get(edge_id).unwrap().nodes();
+    let next = if nodes[0] == *node_id { nodes[1] } else { nodes[nodes.len() - 2] };
+    *graph.nodes().get(&next).unwrap().point()
+}
+
+/// Whether the bearing [x] lies clockwise between [a] and [b]
+fn between(x: f64, a: f64, b: f64) -> bool {
+    (x - a).rem_euclid(360.0) < (b - a).rem_euclid(360.0)
+}
+
+/// The [route] with the [section] replaced by the best way between its
+/// ends, that avoids the edges of the rest of the route
+fn reroute(graph: &Graph, weighting: &Weighting, route: &Path, section: &Section) -> Option<Path> {
+    let nodes = route.nodes();
+    let edges = route.edges();
+    let before = Path::new(nodes[..=section.from].to_vec(), edges[..section.from].to_vec());
+    let mut after = Path::new(nodes[section.to..].to_vec(), edges[section.to..].to_vec());
+
+    let mut overlap = Overlap::new();
+    overlap.add(graph, &before);
+    overlap.add(graph, &after);
+    let mut replacement = a_star(graph, weighting, &overlap, &nodes[section.from], &nodes[section.to])?;
+
+    let mut candidate = before;
+    candidate.append(&mut replacement);
+    candidate.append(&mut after);
+    Some(candidate)
+}
+
+/// The [route] with the [section] ridden the other way around, None if it
+/// contains one-way streets
+fn reverse(graph: &Graph, route: &Path, section: &Section) -> Option<Path> {
+    let directed = route.edges()[section.from..section.to]
+        .iter()
+        .any(|edge_id| *graph.edges().get(edge_id).unwrap().directed());
+    if directed { return None; }
+
+    let mut nodes = route.nodes().clone();
+    let mut edges = route.edges().clone();
+    nodes[section.from..=section.to].reverse();
+    edges[section.from..section.to].reverse();
+    Some(Path::new(nodes, edges))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{Node, Edge};
+
+    /// Undirected graph from node positions and edges given by their ends
+    fn graph(points: &[(f64, f64)], ends: &[(NodeId, NodeId)]) -> Graph {
+        let mut nodes: HashMap<NodeId, Node> = HashMap::new();
+        for (node_id, (x, y)) in points.iter().enumerate() {
+            nodes.insert(node_id as NodeId, Node::new(node_id as NodeId, Point::new(*x, *y)));
+        }
+        let mut edges: HashMap<EdgeId, Edge> = HashMap::new();
+        for (edge_id, (s, t)) in ends.iter().enumerate() {
+            let distance = geo::HaversineDistance::haversine_distance(nodes[s].point(), nodes[t].point());
+            edges.insert(edge_id as EdgeId, Edge::new(edge_id as EdgeId, distance, false, vec![*s, *t]));
+            nodes.get_mut(s).unwrap().insert_edge(edge_id as EdgeId);
+            nodes.get_mut(t).unwrap().insert_edge(edge_id as EdgeId);
+        }
+        Graph::new(nodes, edges)
+    }
+
+    #[test]
+    fn analyse_finds_flaws() {
+        // square 0-1-2-3 with the dead end 1-4 and the diagonal 0-2
+        //
+        // 3---2
+        // | / |
+        // 0---1---4
+        let g = graph(
+            &[(7.000, 51.000), (7.001, 51.000), (7.001, 51.001), (7.000, 51.001), (7.002, 51.000)],
+            &[(0, 1), (1, 2), (2, 3), (3, 0), (1, 4), (0, 2)],
+        );
+
+        // out to the dead end and back
+        let spur = Path::new(vec![0, 1, 4, 1, 2, 3, 0], vec![0, 4, 4, 1, 2, 3]);
+        assert_eq!(vec![Flaw::Spur(Section { from: 2, to: 3 })], analyse(&g, &spur));
+
+        // twice along the first half of the square
+        let repeated = Path::new(vec![0, 1, 2, 3, 0, 1, 2, 0], vec![0, 1, 2, 3, 0, 1, 5]);
+        assert_eq!(vec![Flaw::Repeated(Section { from: 4, to: 6 })], analyse(&g, &repeated));
+
+        // from the dead end once around the square and back
+        let lollipop = Path::new(vec![4, 1, 2, 3, 0, 1, 4], vec![4, 1, 2, 3, 0, 4]);
+        assert_eq!(vec![Flaw::Lollipop(Section { from: 5, to: 6 })], analyse(&g, &lollipop));
+    }
+
+    #[test]
+    fn repair_spur_and_figure_eight() {
+        // two triangles touching at 0
+        //
+        // 1   3
+        //   0
+        // 2   4
+        let g = graph(
+            &[(7.000, 51.000), (6.999, 51.001), (6.999, 50.999), (7.001, 51.001), (7.001, 50.999)],
+            &[(1, 0), (0, 4), (4, 3), (3, 0), (0, 2), (2, 1)],
+        );
+
+        // 1 -> 0 -> 4 -> 3 -> 0 -> 2 -> 1 crosses itself at 0
+        let eight = Path::new(vec![1, 0, 4, 3, 0, 2, 1], vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(vec![Flaw::Crossing(Section { from: 1, to: 4 })], analyse(&g, &eight));
+        let repaired = repair(&g, &Weighting::flat(), &eight, f64::MAX);
+        assert_eq!(&vec![1, 0, 3, 4, 0, 2, 1], repaired.nodes());
+        assert!(analyse(&g, &repaired).is_empty());
+
+        // 1 -> 0 -> 3 and the same way back, the other triangle is a detour
+        let spur = Path::new(vec![1, 0, 3, 0, 1], vec![0, 3, 3, 0]);
+        let repaired = repair(&g, &Weighting::flat(), &spur, f64::MAX);
+        assert_eq!(&vec![1, 0, 3, 4, 0, 2, 1], repaired.nodes());
+
+        // the detour is too long
+        let repaired = repair(&g, &Weighting::flat(), &spur, length(&g, &spur) + 10.0);
+        assert_eq!(&spur, &repaired);
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+
+use geo::HaversineDistance;
+
+use crate::graph::{EdgeId, Graph, NodeId};
+use crate::router::{route_through, Direction, Path, Plan, Stops, Weighting};
+
+/// Changes of the waypoints, before the best loop so far is taken
+const MAX_ITERATIONS: usize = 40;
+/// Unused candidates looked at for every insertion, in their given order
+const SAMPLE: usize = 12;
+/// Share of the loop, that may be ridden twice
+const MAX_REPEATED: f64 = 0.25;
+/// Ridden distance per meter of beeline, until the first loop is known
+const DETOUR_FACTOR: f64 = 1.3;
+
+/// Trip from the start through the via points and some of the [candidates]
+/// to the end of the [stops], that is as long as the [window] (min, max) in
+/// meters asks for. It is a loop, if the end is the start.
+///
+/// Waypoints are added where they lengthen the trip the least (cheapest
+/// insertion) and removed again, if the trip got too long. Via points are
+/// inserted first and never removed. The beeline detour of a change is
+/// scaled by the ratio of ridden to beeline distance of the current trip,
+/// so every step aims at the middle of the window.
+/// Earlier candidates are preferred, loops are ridden in [direction]. Gives
+/// up after [MAX_ITERATIONS] and returns the trip closest to the window,
+/// that repeats the least
+pub fn roundtrip(
+    graph: &Graph,
+    weighting: &Weighting,
+    stops: &Stops,
+    candidates: &[NodeId],
+    window: (f64, f64),
+    direction: &Direction
+) -> Plan {
+    let (start, end) = (&stops.start, &stops.end);
+    let target = (window.0 + window.1) / 2.0;
+    let mut waypoints: Vec<NodeId> = Vec::new();
+    for node_id in &stops.via {
+        if waypoints.contains(node_id) || node_id == start || node_id == end { continue; }
+        let (position, _) = cheapest_insertion(graph, start, end, &waypoints, node_id);
+        waypoints.insert(position, *node_id);
+    }
+    // candidates, that made the loop too long, are not tried again
+    let mut tabu: HashSet<NodeId> = HashSet::from([*start, *end]);
+    let mut best: Option<((f64, f64), Plan)> = None;
+
+    for _ in 0..MAX_ITERATIONS {
+        // the beeline detours do not depend on the way round
+        if start == end && Direction::of(graph, start, &waypoints).is_some_and(|d| d != *direction) { waypoints.reverse(); }
+        let (route, skipped) = route_through(graph, weighting, &waypoints, start, end);
+        let (length, repeated) = measure(graph, &route);
+
+        // meters outside of the window matter most, then the ones ridden twice
+        let penalty = (
+            (window.0 - length).max(0.0) + (length - window.1).max(0.0),
+            (repeated - length * MAX_REPEATED).max(0.0),
+        );
+        if best.as_ref().is_none_or(|(b, _)| penalty.0.total_cmp(&b.0).then(penalty.1.total_cmp(&b.1)).is_lt()) {
+            best = Some((penalty, Plan { route, fits: penalty.0 == 0.0, skipped }));
+        }
+        if penalty == (0.0, 0.0) { break; }
+
+        let beeline = beeline_length(graph, start, end, &waypoints);
+        let factor = if beeline > 0.0 { length / beeline } else { DETOUR_FACTOR };
+
+        if length > window.1 {
+            // drop the waypoint, whose removal comes closest to the target
+            let excess = length - target;
+            let i = (0..waypoints.len())
+                .filter(|i| ! stops.via.contains(&waypoints[*i]))
+                .min_by(|a, b| {
+                    let error = |i: &usize| (saving(graph, start, end, &waypoints, *i) * factor - excess).abs();
+                    error(a).total_cmp(&error(b))
+                });
+            match i {
+                Some(i) => { tabu.insert(waypoints.remove(i)); },
+                None => break,
+            }
+        } else {
+            // add the candidate, whose detour comes closest to the target,
+            // more waypoints also widen a loop, that is ridden there and back
+            let missing = target - length;
+            let insertion = candidates
+                .iter()
+                .filter(|c| ! tabu.contains(c) && ! waypoints.contains(c))
+                .take(SAMPLE)
+                .map(|c| (*c, cheapest_insertion(graph, start, end, &waypoints, c)))
+                .min_by(|(_, (_, a)), (_, (_, b))| (a * factor - missing).abs().total_cmp(&(b * factor - missing).abs()));
+            match insertion {
+                Some((c, (position, _))) => waypoints.insert(position, c),
+                None => break,
+            }
+        }
+    }
+
+    best.map(|(_, plan)| plan).unwrap_or_default()
+}
+
+/// Ridden meters of the [route] and how many of them have been ridden before
+fn measure(graph: &Graph, route: &Path) -> (f64, f64) {
+    let mut ridden: HashMap<EdgeId, usize> = HashMap::new();
+    let mut length = 0.0;
+    let mut repeated = 0.0;
+    for edge_id in route.edges() {
+        let distance = graph.edges().get(edge_id).unwrap().distance();
+        let count = ridden.entry(*edge_id).or_insert(0);
+        *count += 1;
+        if *count > 1 { repeated += distance; }
+        length += distance;
+    }
+    (length, repeated)
+}
+
+fn beeline(graph: &Graph, a: &NodeId, b: &NodeId) -> f64 {
+    graph.nodes().get(a).unwrap().point().haversine_distance(graph.nodes().get(b).unwrap().point())
+}
+
+/// Neighbours of the waypoint at [i] along the trip, that begins at
+/// [start] and ends at [end]
+fn neighbours<'a>(start: &'a NodeId, end: &'a NodeId, waypoints: &'a [NodeId], i: usize) -> (&'a NodeId, &'a NodeId) {
+    let before = if i == 0 { start } else { &waypoints[i - 1] };
+    let after = waypoints.get(i).unwrap_or(end);
+    (before, after)
+}
+
+/// Beeline length of the trip from [start] through all [waypoints] to [end]
+fn beeline_length(graph: &Graph, start: &NodeId, end: &NodeId, waypoints: &[NodeId]) -> f64 {
+    (0..=waypoints.len())
+        .map(|i| {
+            let (before, after) = neighbours(start, end, waypoints, i);
+            beeline(graph, before, after)
+        })
+        .sum()
+}
+
+/// Beeline meters saved by skipping the waypoint at [i]
+fn saving(graph: &Graph, start: &NodeId, end: &NodeId, waypoints: &[NodeId], i: usize) -> f64 {
+    let (before, _) = neighbours(start, end, waypoints, i);
+    let (_, after) = neighbours(start, end, waypoints, i + 1);
+    beeline(graph, before, &waypoints[i]) + beeline(graph, &waypoints[i], after) - beeline(graph, before, after)
+}
+
+/// Position in the [waypoints], where [candidate] lengthens the trip the
+/// least, together with that beeline detour
+fn cheapest_insertion(graph: &Graph, start: &NodeId, end: &NodeId, waypoints: &[NodeId], candidate: &NodeId) -> (usize, f64) {
+    (0..=waypoints.len())
+        .map(|i| {
+            let (before, after) = neighbours(start, end, waypoints, i);
+            let detour = beeline(graph, before, candidate) + beeline(graph, candidate, after) - beeline(graph, before, after);
+            (i, detour)
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{data_from_pbf, weave};
+    use crate::router::closest_intersection;
+    use geo::Point;
+
+    #[test]
+    fn cheapest_insertion_between_neighbours() {
+        // start 0 and waypoints 1, 2 on a square, 3 lies between 1 and 2
+        let data_nodes = [(0, 7.000, 51.000), (1, 7.000, 51.010), (2, 7.010, 51.010), (3, 7.005, 51.011)];
+        let mut nodes = HashMap::new();
+        for (node_id, x, y) in data_nodes {
+            nodes.insert(node_id, crate::graph::Node::new(node_id, Point::new(x, y)));
+        }
+        let graph = Graph::new(nodes, HashMap::new());
+
+        let (position, detour) = cheapest_insertion(&graph, &0, &0, &[1, 2], &3);
+        assert_eq!(1, position);
+        assert!(detour > 0.0 && detour < 50.0);
+        assert!((saving(&graph, &0, &0, &[1, 3, 2], 1) - detour).abs() < 1e-6);
+    }
+
+    #[test]
+    fn roundtrip_hits_window() {
+        let data = data_from_pbf("resources/dortmund_sued.osm.pbf");
+        let graph = weave(&data);
+        let everywhere: HashSet<NodeId> = graph.nodes().keys().copied().collect();
+
+        let start = closest_intersection(&graph, &Point::new(7.4058927, 51.4896161), &everywhere).unwrap();
+        let reachable = graph.component_of(&start);
+        // intersections on a grid around the start
+        let candidates: Vec<NodeId> = (0..49)
+            .map(|i| Point::new(7.385 + (i % 7) as f64 * 0.007, 51.476 + (i / 7) as f64 * 0.005))
+            .map(|p| closest_intersection(&graph, &p, &reachable).unwrap())
+            .collect();
+
+        let Plan { route, fits, skipped } = roundtrip(&graph, &Weighting::flat(), &Stops { start, via: Vec::new(), end: start }, &candidates, (9_000.0, 11_000.0), &Direction::Clockwise);
+        assert!(fits);
+        assert!(skipped.is_empty());
+        assert_eq!(route.nodes().first(), route.nodes().last());
+        let (length, _) = measure(&graph, &route);
+        assert!(length >= 9_000.0 && length <= 11_000.0, "length {}", length);
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+
+use geo::Point;
+
+use crate::graph::{Graph, NodeId};
+use crate::router::{nearest_graph_nodes, route_through, Direction, Plan, Weighting};
+
+/// Meters per degree of latitude
+const METERS_PER_DEGREE: f64 = 111_320.0;
+/// Points on the outline, the route is led through, besides the start
+const ANCHORS: usize = 8;
+/// Samples of the outline to measure its circumference
+const SAMPLES: usize = 360;
+/// Routes planned with a rescaled outline, before the closest one is taken
+const MAX_ITERATIONS: usize = 10;
+/// Ridden distance per meter of outline, before the first loop is known
+const DETOUR_FACTOR: f64 = 1.3;
+
+/// Outline of a loop planned without pois
+#[derive(serde::Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    /// The start is on the circle
+    #[default]
+    Circle,
+    /// Twice as long as wide, the start is at one end
+    Ellipse,
+    /// Pointed at the start and round at the far end
+    Teardrop,
+}
+
+/// [Shape] of a loop together with the direction it extends to from the
+/// start
+pub struct Outline {
+    pub shape: Shape,
+    /// Degrees, 0 is north
+    pub heading: f64,
+}
+
+impl Shape {
+    /// Position on the outline of unit size at [t] from 0 to 2π as meters
+    /// along the heading and to its left. The start is at t = 0
+    fn unit(&self, t: f64) -> (f64, f64) {
+        let along = 1.0 - t.cos();
+        let across = match self {
+            Self::Circle => t.sin(),
+            Self::Ellipse => 0.5 * t.sin(),
+            Self::Teardrop => t.sin() * (t / 2.0).sin(),
+        };
+        (along, across)
+    }
+
+    /// Circumference of the outline of unit size
+    fn unit_length(&self) -> f64 {
+        (0..SAMPLES)
+            .map(|i| {
+                let (a, b) = (self.unit(step(i, SAMPLES)), self.unit(step(i + 1, SAMPLES)));
+                ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+            })
+            .sum()
+    }
+
+    /// [ANCHORS] points on the outline with a circumference of [length]
+    /// meters, that begins at [start] and extends to [heading] degrees (0 is
+    /// north). They follow each other in [direction]
+    pub fn anchors(&self, start: &Point, heading: f64, length: f64, direction: &Direction) -> Vec<Point> {
+        let scale = length / self.unit_length();
+        let heading = heading.to_radians();
+        // clockwise the loop turns to the left first
+        let side = match direction {
+            Direction::Clockwise => 1.0,
+            Direction::Counterclockwise => -1.0,
+        };
+
+        (1..=ANCHORS)
+            .map(|i| {
+                let (along, across) = self.unit(step(i, ANCHORS + 1));
+                let (along, across) = (along * scale, across * scale * side);
+                // meters to the east and to the north, left is heading - 90°
+                let east = along * heading.sin() - across * heading.cos();
+                let north = along * heading.cos() + across * heading.sin();
+                Point::new(
+                    start.x() + east / (METERS_PER_DEGREE * start.y().to_radians().cos()),
+                    start.y() + north / METERS_PER_DEGREE,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Angle of the [i]th of [n] equal steps around a full turn
+fn step(i: usize, n: usize) -> f64 {
+    i as f64 / n as f64 * std::f64::consts::TAU
+}
+
+/// Loop from [start] along the [outline], that is as long as the [window]
+/// (min, max) in meters asks for. The anchors of the outline are snapped to
+/// the [reachable] intersections of the component of the start and the
+/// outline is rescaled by the ratio of wished to ridden length, until
+/// the loop fits or stops changing, e.g. at the border of the map. Fails,
+/// if no intersection can be reached from the start
+pub fn shaped(
+    graph: &Graph,
+    weighting: &Weighting,
+    start: &NodeId,
+    reachable: &HashSet<NodeId>,
+    outline: &Outline,
+    window: (f64, f64),
+    direction: &Direction
+) -> Result<Plan, String> {
+    let target = (window.0 + window.1) / 2.0;
+    let start_point = *graph.nodes().get(start).unwrap().point();
+
+    let mut circumference = target / DETOUR_FACTOR;
+    let mut best: Option<(f64, Plan)> = None;
+    let mut previous: Option<f64> = None;
+    for _ in 0..MAX_ITERATIONS {
+        let mut waypoints = nearest_graph_nodes(graph, &outline.shape.anchors(&start_point, outline.heading, circumference, direction), reachable)?;
+        // close anchors may share their intersection
+        waypoints.dedup();
+        waypoints.retain(|node_id| node_id != start);
+
+        let (route, skipped) = route_through(graph, weighting, &waypoints, start, start);
+        let length = route.length(graph);
+
+        let off = (window.0 - length).max(0.0) + (length - window.1).max(0.0);
+        if best.as_ref().is_none_or(|(b, _)| off < *b) { best = Some((off, Plan { route, fits: off == 0.0, skipped })); }
+        if off == 0.0 || length == 0.0 || previous == Some(length) { break; }
+        previous = Some(length);
+        circumference *= (target / length).clamp(0.5, 2.0);
+    }
+
+    Ok(best.map(|(_, plan)| plan).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use geo::HaversineDistance;
+    use crate::graph::Node;
+
+    #[test]
+    fn anchors_on_outline() {
+        let start = Point::new(7.0, 51.0);
+        for shape in [Shape::Circle, Shape::Ellipse, Shape::Teardrop] {
+            let anchors = shape.anchors(&start, 315.0, 10_000.0, &Direction::Clockwise);
+            assert_eq!(ANCHORS, anchors.len());
+
+            // the loop through the anchors is a bit shorter than the outline
+            let mut ring = vec![start];
+            ring.extend(anchors.iter());
+            ring.push(start);
+            let length: f64 = ring.windows(2).map(|p| p[0].haversine_distance(&p[1])).sum();
+            assert!(length > 9_000.0 && length < 10_000.0, "{:?} is {} m long", shape, length);
+
+            // the far end is to the north west
+            let far = anchors[ANCHORS / 2 - 1];
+            assert!(far.x() < start.x() && far.y() > start.y());
+        }
+
+        // the circle's diameter is its circumference divided by π
+        let anchors = Shape::Circle.anchors(&start, 90.0, 10_000.0, &Direction::Clockwise);
+        let far = Point::new(start.x() + 10_000.0 / std::f64::consts::PI / (METERS_PER_DEGREE * 51.0_f64.to_radians().cos()), 51.0);
+        assert!(anchors.iter().all(|a| a.haversine_distance(&far) < 3_200.0));
+    }
+
+    #[test]
+    fn anchors_in_direction() {
+        let start = Point::new(7.0, 51.0);
+        let mut nodes: HashMap<NodeId, Node> = HashMap::new();
+        nodes.insert(0, Node::new(0, start));
+        for direction in [Direction::Clockwise, Direction::Counterclockwise] {
+            for (i, anchor) in Shape::Teardrop.anchors(&start, 45.0, 5_000.0, &direction).iter().enumerate() {
+                nodes.insert(i as NodeId + 1, Node::new(i as NodeId + 1, *anchor));
+            }
+            let graph = Graph::new(std::mem::take(&mut nodes), HashMap::new());
+            let waypoints: Vec<NodeId> = (1..=ANCHORS as NodeId).collect();
+            assert_eq!(Some(direction), Direction::of(&graph, &0, &waypoints));
+
+            nodes.insert(0, Node::new(0, start));
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use crate::graph::{Edge, EdgeId, Graph};
+use crate::profile::Profile;
+use crate::router::Path;
+
+/// Translates an edge into the costs of riding along it
+pub struct Weighting {
+    /// Additional cost in meters for every meter of ascent, a negative value
+    /// prefers climbs over flat roads
+    gradient_penalty: f64,
+    /// Edges that are steeper uphill are not ridden in that direction
+    max_gradient: f64,
+}
+
+impl Weighting {
+    pub fn new(profile: &Profile, gradient_penalty: f64) -> Self {
+        Self {
+            gradient_penalty,
+            max_gradient: profile.max_gradient(),
+        }
+    }
+
+    /// Only the distance counts, every edge is rideable
+    pub fn flat() -> Self {
+        Self {
+            gradient_penalty: 0.0,
+            max_gradient: f64::MAX,
+        }
+    }
+
+    /// Costs to ride along [edge], going s-->t if [forward] and t-->s
+    /// otherwise. None if the edge is too steep in this direction
+    pub fn cost(&self, edge: &Edge, forward: bool) -> Option<f64> {
+        let distance = *edge.distance();
+        let ascent = edge.ascent(forward);
+        if distance > 0.0 && ascent / distance > self.max_gradient { return None; }
+
+        // note: a negative penalty must not make an edge free of charge,
+        // otherwise a_star would circle around hills
+        Some((distance + self.gradient_penalty * ascent).max(distance * MIN_COST_FACTOR))
+    }
+
+    /// Lowest cost per meter of any edge, scales the beeline estimate of
+    /// a_star, so it never overestimates the remaining costs
+    pub fn min_cost_factor(&self) -> f64 {
+        if self.gradient_penalty < 0.0 { MIN_COST_FACTOR } else { 1.0 }
+    }
+
+    /// Make climbs cheaper, so following routes contain more ascent
+    pub fn prefer_climbs(&mut self) {
+        self.gradient_penalty = (self.gradient_penalty - PENALTY_STEP).max(MIN_PENALTY);
+    }
+
+    /// Make climbs more expensive, so following routes contain less ascent
+    pub fn avoid_climbs(&mut self) {
+        self.gradient_penalty += PENALTY_STEP;
+    }
+}
+
+/// Edges ridden by a route so far, riding them again costs extra, so the
+/// route avoids repetition, where there is a reasonable alternative
+#[derive(Default)]
+pub struct Overlap {
+    /// whether an edge has been ridden s-->t and t-->s
+    ridden: HashMap<EdgeId, (bool, bool)>,
+}
+
+impl Overlap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember every edge of the [path] together with its direction
+    pub fn add(&mut self, graph: &Graph, path: &Path) {
+        for (from, edge_id) in path.nodes().iter().zip(path.edges()) {
+            let forward = graph.edges().get(edge_id).unwrap().s() == from;
+            let ridden = self.ridden.entry(*edge_id).or_insert((false, false));
+            if forward { ridden.0 = true; } else { ridden.1 = true; }
+        }
+    }
+
+    /// Factor on the costs of riding [edge_id] s-->t if [forward] and t-->s
+    /// otherwise. Riding back the same way is worse than following a road
+    /// a second time
+    pub fn factor(&self, edge_id: &EdgeId, forward: bool) -> f64 {
+        let (same, opposite) = match self.ridden.get(edge_id) {
+            Some((s, t)) if forward => (*s, *t),
+            Some((s, t)) => (*t, *s),
+            None => return 1.0,
+        };
+        if opposite { return OPPOSITE_DIRECTION_FACTOR; }
+        if same { return SAME_DIRECTION_FACTOR; }
+        1.0
+    }
+}
+
+/// Change of the gradient penalty when the ascent of a route was off target
+const PENALTY_STEP: f64 = 2.0;
+/// Even when looking for climbs, a meter uphill is worth more than nothing
+const MIN_PENALTY: f64 = -3.0;
+/// Share of its distance, an edge costs at least, even uphill with a
+/// negative penalty
+const MIN_COST_FACTOR: f64 = 0.1;
+/// Riding an edge once more in the same direction costs this many times
+const SAME_DIRECTION_FACTOR: f64 = 3.0;
+/// Riding an edge back the way the route came costs this many times
+const OPPOSITE_DIRECTION_FACTOR: f64 = 5.0;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cost_depends_on_direction() {
+        // 100m long, 12m up when going s-->t
+        let mut edge = Edge::new(0, 100.0, false, vec![0, 1]);
+        edge.set_climb(12.0, 0.0);
+
+        let weighting = Weighting::new(&Profile::Road, 10.0);
+        assert_eq!(Some(220.0), weighting.cost(&edge, true));
+        assert_eq!(Some(100.0), weighting.cost(&edge, false));
+
+        // too steep for a loaded touring bike, but fine downhill
+        let weighting = Weighting::new(&Profile::Trekking, 10.0);
+        assert_eq!(None, weighting.cost(&edge, true));
+        assert_eq!(Some(100.0), weighting.cost(&edge, false));
+
+        // looking for climbs uphill is cheaper than the distance
+        let mut weighting = Weighting::new(&Profile::Road, 0.0);
+        assert_eq!(1.0, weighting.min_cost_factor());
+        weighting.prefer_climbs();
+        weighting.prefer_climbs();
+        assert_eq!(Some(64.0), weighting.cost(&edge, true));
+        assert_eq!(MIN_COST_FACTOR, weighting.min_cost_factor());
+    }
+
+    #[test]
+    fn overlap_depends_on_direction() {
+        use geo::Point;
+        use crate::graph::Node;
+
+        // 0<-->1<-->2, ridden from 0 to 2
+        let mut nodes = HashMap::new();
+        for node_id in 0..3 { nodes.insert(node_id, Node::new(node_id, Point::new(node_id as f64, 0.0))); }
+        let mut edges = HashMap::new();
+        edges.insert(0, Edge::new(0, 1.0, false, vec![0, 1]));
+        edges.insert(1, Edge::new(1, 1.0, false, vec![2, 1]));
+        let graph = Graph::new(nodes, edges);
+
+        let mut overlap = Overlap::new();
+        overlap.add(&graph, &Path::new(vec![0, 1, 2], vec![0, 1]));
+        assert_eq!(SAME_DIRECTION_FACTOR, overlap.factor(&0, true));
+        assert_eq!(OPPOSITE_DIRECTION_FACTOR, overlap.factor(&0, false));
+        // the second edge is drawn from 2 to 1
+        assert_eq!(SAME_DIRECTION_FACTOR, overlap.factor(&1, false));
+        assert_eq!(OPPOSITE_DIRECTION_FACTOR, overlap.factor(&1, true));
+        assert_eq!(1.0, overlap.factor(&2, true));
+    }
+}